@@ -0,0 +1,139 @@
+use std::io;
+use vcs::{CloneOptions, VCS};
+
+
+// A line of the list given to `clone --from-file` or `clone --stdin`.
+//
+// ```text
+// # comment
+// github.com/hoge/fuga
+// hoge/piyo branch=develop depth=1
+// https://hg.example.com/hoge/foo vcs=hg
+// ```
+#[derive(Debug, PartialEq)]
+pub struct Entry {
+  pub query: String,
+  pub branch: Option<String>,
+  pub depth: Option<u32>,
+  pub vcs: Option<VCS>,
+}
+
+impl Entry {
+  pub fn new(query: &str) -> Entry {
+    Entry {
+      query: query.to_owned(),
+      branch: None,
+      depth: None,
+      vcs: None,
+    }
+  }
+
+  // override the options given in the command line.
+  pub fn options(&self, base: &CloneOptions) -> CloneOptions {
+    CloneOptions {
+      depth: self.depth.or(base.depth),
+      branch: self.branch.clone().or(base.branch.clone()),
+      ..base.clone()
+    }
+  }
+}
+
+pub fn parse(content: &str) -> io::Result<Vec<Entry>> {
+  let mut entries: Vec<Entry> = Vec::new();
+
+  for (lineno, line) in content.lines().enumerate() {
+    // `#` starts a comment only at the beginning of a line or after whitespace, since it may be
+    // a part of URLs and branch names (e.g. `branch=fix#12`).
+    let comment = line.char_indices()
+      .find(|&(i, c)| c == '#' && line[..i].chars().next_back().map_or(true, char::is_whitespace))
+      .map(|(i, _)| i);
+    let line = comment.map(|pos| &line[..pos]).unwrap_or(line);
+    let mut tokens = line.split_whitespace();
+    let query = match tokens.next() {
+      Some(query) => query,
+      None => continue,
+    };
+
+    let mut entry = Entry::new(query);
+    for token in tokens {
+      let mut kv = token.splitn(2, '=');
+      match (kv.next(), kv.next()) {
+        (Some("branch"), Some(branch)) => entry.branch = Some(branch.to_owned()),
+        (Some("depth"), Some(depth)) => {
+          entry.depth = Some(depth.parse()
+            .ok()
+            .and_then(|n| if n > 0 { Some(n) } else { None })
+            .ok_or(invalid(lineno, "the depth must be a positive integer"))?)
+        }
+        (Some("vcs"), Some(vcs)) => {
+          entry.vcs = Some(vcs.parse().map_err(|_| invalid(lineno, "unknown VCS"))?)
+        }
+        _ => return Err(invalid(lineno, &format!("unknown option '{}'", token))),
+      }
+    }
+
+    // keep the first one if the same query appears more than once.
+    if !entries.iter().any(|e| e.query == entry.query) {
+      entries.push(entry);
+    }
+  }
+
+  Ok(entries)
+}
+
+fn invalid(lineno: usize, msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData,
+                 format!("line {}: {}", lineno + 1, msg))
+}
+
+
+#[cfg(test)]
+mod test_parse {
+  use super::{Entry, parse};
+  use vcs::{CloneOptions, VCS};
+
+  #[test]
+  fn entries() {
+    let entries = parse("# repositories\n\
+                         \n\
+                         github.com/hoge/fuga\n\
+                         hoge/piyo branch=develop depth=1  # shallow\n\
+                         https://hg.example.com/hoge/foo vcs=hg\n\
+                         github.com/hoge/fuga\n")
+      .unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].query, "github.com/hoge/fuga");
+    assert_eq!(entries[1],
+               Entry {
+                 query: "hoge/piyo".to_owned(),
+                 branch: Some("develop".to_owned()),
+                 depth: Some(1),
+                 vcs: None,
+               });
+    assert_eq!(entries[2].vcs, Some(VCS::Hg));
+
+    let base = CloneOptions { depth: Some(3), bare: true, ..CloneOptions::default() };
+    let opts = entries[1].options(&base);
+    assert_eq!(opts.depth, Some(1));
+    assert_eq!(opts.branch, Some("develop".to_owned()));
+    assert!(opts.bare);
+  }
+
+  #[test]
+  fn comments() {
+    let entries = parse("#comment\n\
+                         hoge/fuga branch=fix#12\t# comment\n\
+                         https://example.com/hoge/piyo.git#main\n")
+      .unwrap();
+    assert_eq!(entries.len(), 2);
+    assert_eq!(entries[0].branch, Some("fix#12".to_owned()));
+    assert_eq!(entries[1].query, "https://example.com/hoge/piyo.git#main");
+  }
+
+  #[test]
+  fn invalid_options() {
+    assert!(parse("hoge/fuga depth=0").is_err());
+    assert!(parse("hoge/fuga vcs=cvs").is_err());
+    assert!(parse("hoge/fuga --branch").is_err());
+  }
+}
@@ -1,46 +1,287 @@
 extern crate ghq;
 extern crate clap;
 
-use std::io::{self, Write};
-use clap::{Arg, App, AppSettings, SubCommand};
+use std::env;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use clap::{Arg, App, AppSettings, ArgMatches, SubCommand};
+use ghq::batch::{self, Entry};
 use ghq::config::Config;
 use ghq::workspace::Workspace;
 use ghq::error::GhqError;
+use ghq::format::{Format, RepoRecord, RootRecord, StatusRecord};
+use ghq::query::{Query, unique_names};
+use ghq::jobs;
+use ghq::manifest::{self, Manifest};
+use ghq::repository::{Outcome, Repository};
+use ghq::util::{self, Output};
+use ghq::vcs::{CloneOptions, State};
 
 fn main() {
   match _main() {
     Ok(exitcode) => std::process::exit(exitcode),
-    Err(err) => writeln!(&mut io::stderr(), "IO Error: {}", err.to_string()).unwrap(),
+    Err(err) => {
+      writeln!(&mut io::stderr(), "Error: {}", err.to_string()).unwrap();
+      std::process::exit(1);
+    }
   }
 }
 
 fn _main() -> Result<i32, GhqError> {
-  let config = Config::load()?;
+  let matches = cli().get_matches();
+
+  let mut config = Config::load()?;
+  if let ("clone", Some(m)) = matches.subcommand() {
+    if m.is_present("ssh") {
+      config.ssh = Some(true);
+    }
+  }
   let workspace = Workspace::new(config);
 
-  let matches = cli().get_matches();
   match matches.subcommand() {
     ("clone", Some(m)) => {
-      let queries = m.values_of("query").unwrap();
-      for ref s in queries {
-        workspace.clone_from(s)?;
+      let root = workspace.select_root(m.value_of("root"), m.is_present("allow-unregistered"))?;
+      let vcs = m.value_of("vcs").and_then(|s| s.parse().ok());
+      let opts = clone_options(m)?;
+      let update = m.is_present("update");
+
+      let mut entries: Vec<_> = m.values_of("query")
+        .map(|queries| queries.map(Entry::new).collect())
+        .unwrap_or_default();
+      if let Some(path) = m.value_of("from-file") {
+        entries.extend(batch::parse(&util::read_content(path)?)?);
       }
+      if m.is_present("stdin") {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        entries.extend(batch::parse(&content)?);
+      }
+
+      let mut results = Vec::new();
+      let mut targets: Vec<(PathBuf, Repository, CloneOptions)> = Vec::new();
+      for entry in entries {
+        let repo = match workspace.resolve_remote(&entry.query, entry.vcs.or(vcs)) {
+          Ok(repo) => repo,
+          Err(err) => {
+            results.push((entry.query, Err(err)));
+            continue;
+          }
+        };
+        if targets.iter().any(|&(_, ref r, _)| r.full_name() == repo.full_name()) {
+          continue;
+        }
+        // repositories in another root are not cloned again.
+        let root = match workspace.locate(&repo) {
+          Some(_) if !update => {
+            results.push((repo.full_name(), Ok(Outcome::Skipped)));
+            continue;
+          }
+          Some(existing) => existing.to_owned(),
+          None => root.clone(),
+        };
+        targets.push((root, repo, entry.options(&opts)));
+      }
+
+      let clone = move |root: &Path, repo: &Repository, opts: &CloneOptions, out: &mut Output| {
+        repo.clone_into(root, opts, update, out)
+      };
+      results.extend(run_jobs(targets, num_jobs(m)?, clone));
+      return Ok(report(&results));
     }
-    ("list", Some(_)) => {
-      workspace.map_repo(|repo, root| {
-        let repo_path = repo.local_path(root).to_string_lossy().replace("\\", "/");
-        println!("{}", repo_path);
-      });
+    ("import", Some(m)) => {
+      let root = workspace.select_root(m.value_of("root"), m.is_present("allow-unregistered"))?;
+      let mut out = Output::Inherit;
+      let results: Results = m.values_of("dir")
+        .unwrap()
+        .map(|dir| {
+          let result = workspace.import(Path::new(dir),
+                                        &root,
+                                        m.is_present("link"),
+                                        m.is_present("dry-run"),
+                                        &mut out);
+          (dir.to_owned(), result)
+        })
+        .collect();
+      return Ok(report(&results));
     }
-    ("root", Some(m)) => {
-      if m.is_present("all") {
-        for root in workspace.roots() {
-          println!("{}", root.display());
+    ("export", Some(m)) => {
+      let manifest = Manifest::collect(&workspace);
+      match m.value_of("format") {
+        Some("json") => println!("{}", manifest.to_json()),
+        _ => print!("{}", manifest.to_toml()),
+      }
+    }
+    ("import-manifest", Some(m)) => {
+      let manifest = Manifest::parse(&util::read_content(m.value_of("file").unwrap())?)?;
+      let default_root = workspace.select_root(m.value_of("root"),
+                                               m.is_present("allow-unregistered"))?;
+      let checkout = m.is_present("checkout");
+
+      let mut results = Vec::new();
+      let mut targets = Vec::new();
+      for entry in manifest.repos {
+        let repo = match entry.repository(&workspace) {
+          Ok(repo) => repo,
+          Err(err) => {
+            results.push((format!("{}/{}", entry.host, entry.path), Err(err)));
+            continue;
+          }
+        };
+        // the recorded root is used unless `--root` is given.
+        let root = match entry.root {
+          Some(ref name) if !m.is_present("root") => {
+            workspace.select_root(Some(name), false).unwrap_or(default_root.clone())
+          }
+          _ => default_root.clone(),
+        };
+        let revision = if checkout { entry.revision.clone() } else { None };
+        targets.push((root, repo, (entry.clone_options(), revision)));
+      }
+
+      let restore = |root: &Path,
+                     repo: &Repository,
+                     &(ref opts, ref revision): &(CloneOptions, Option<String>),
+                     out: &mut Output| {
+        manifest::restore(repo, root, opts, revision.as_ref().map(|s| s.as_str()), out)
+      };
+      results.extend(run_jobs(targets, num_jobs(m)?, restore));
+      return Ok(report(&results));
+    }
+    ("update", Some(m)) => {
+      let queries: Vec<_> = m.values_of("query")
+        .map(|q| q.map(|s| Query::new(Some(s))).collect())
+        .unwrap_or(vec![Query::new(None)]);
+      let targets: Vec<_> = workspace.query(&Query::new(None))
+        .into_iter()
+        .filter(|&(_, repo)| queries.iter().any(|q| q.matches(repo)))
+        .map(|(root, repo)| (root.to_owned(), repo.clone(), ()))
+        .collect();
+
+      let results = run_jobs(targets, num_jobs(m)?, |root, repo, _, out| repo.update(root, out));
+      return Ok(report(&results));
+    }
+    ("list", Some(m)) => {
+      let query = Query::new(m.value_of("query"))
+        .exact(m.is_present("exact"))
+        .vcs(m.value_of("vcs").and_then(|s| s.parse().ok()));
+      let repos = workspace.query(&query);
+
+      if let Some(format) = m.value_of("format") {
+        let format = Format::new(format, m.value_of("template"))?;
+        let records: Vec<_> = repos.iter()
+          .map(|&(root, repo)| RepoRecord::new(repo, root, m.is_present("status")))
+          .collect();
+        println!("{}", format.render(&records));
+        return Ok(0);
+      }
+
+      let names: Vec<_> = if m.is_present("full-path") {
+        repos.iter()
+          .map(|&(root, repo)| repo.local_path(root).to_string_lossy().replace("\\", "/"))
+          .collect()
+      } else {
+        repos.iter().map(|&(_, repo)| repo.full_name()).collect()
+      };
+      let names = if m.is_present("unique") {
+        unique_names(&names)
+      } else {
+        names
+      };
+
+      if m.is_present("url") {
+        for (name, &(root, repo)) in names.iter().zip(repos.iter()) {
+          let url = repo.remote_url(root).map(|url| url.as_str().to_owned());
+          println!("{}\t{}", name, url.unwrap_or_default());
+        }
+      } else {
+        for name in names {
+          println!("{}", name);
+        }
+      }
+    }
+    ("check", Some(m)) => {
+      let query = Query::new(m.value_of("query"));
+      let mut mismatched = 0;
+      for (root, repo) in workspace.query(&query) {
+        if let Some(remote) = repo.remote_mismatch(root, workspace.hosts()) {
+          mismatched += 1;
+          println!("{}: the remote '{}' belongs to '{}'",
+                   repo.full_name(),
+                   remote.url().map(|url| url.as_str()).unwrap_or_default(),
+                   remote.full_name());
+        }
+      }
+      return Ok(if mismatched > 0 { 1 } else { 0 });
+    }
+    ("status", Some(m)) => {
+      let states: Vec<State> = m.values_of("state")
+        .map(|states| states.filter_map(|s| s.parse().ok()).collect())
+        .unwrap_or_default();
+      let records: Vec<_> = workspace.query(&Query::new(m.value_of("query")))
+        .into_iter()
+        .map(|(root, repo)| StatusRecord::new(repo, root))
+        .filter(|record| if m.is_present("all") {
+          true
+        } else if states.is_empty() {
+          !record.states.is_empty()
+        } else {
+          record.states.iter().any(|s| states.contains(s))
+        })
+        .collect();
+
+      if let Some(format) = m.value_of("format") {
+        let format = Format::new(format, m.value_of("template"))?;
+        println!("{}", format.render(&records));
+        return Ok(0);
+      }
+      for record in records {
+        match record.branch {
+          Some(ref branch) => println!("{}  {}  {}", record.name, branch, record.states_str()),
+          None => {
+            println!("{}  ?  {}: {}",
+                     record.name,
+                     record.states_str(),
+                     record.error.clone().unwrap_or_default())
+          }
         }
+      }
+    }
+    ("look", Some(m)) => {
+      let (root, repo) = workspace.lookup(m.value_of("query").unwrap())?;
+      let path = repo.local_path(root);
+      if m.is_present("print") {
+        println!("{}", path.display());
       } else {
-        if let Some(root) = workspace.default_root() {
+        return util::wait_exec(&shell(), &[], Some(&path)).map_err(Into::into);
+      }
+    }
+    ("rm", Some(m)) => {
+      let (root, repo) = workspace.lookup(m.value_of("query").unwrap())?;
+      let path = repo.remove(root, m.is_present("force"))?;
+      println!("removed '{}'", path.display());
+    }
+    ("root", Some(m)) => {
+      if let Some(format) = m.value_of("format") {
+        let format = Format::new(format, m.value_of("template"))?;
+        let default_root = workspace.default_root();
+        let records: Vec<_> = workspace.named_roots()
+          .into_iter()
+          .filter(|&(_, root)| m.is_present("all") || Some(root) == default_root)
+          .map(|(name, root)| {
+            RootRecord {
+              name: name.map(ToOwned::to_owned),
+              path: root.to_string_lossy().replace("\\", "/"),
+              default: Some(root) == default_root,
+            }
+          })
+          .collect();
+        println!("{}", format.render(&records));
+      } else if m.is_present("all") {
+        for root in workspace.roots() {
           println!("{}", root.display());
         }
+      } else if let Some(root) = workspace.default_root() {
+        println!("{}", root.display());
       }
     }
     (_, _) => unreachable!(),
@@ -49,6 +290,94 @@ fn _main() -> Result<i32, GhqError> {
   Ok(0)
 }
 
+type Results = Vec<(String, Result<Outcome, GhqError>)>;
+
+// run operations for repositories concurrently.
+// the outputs of child processes are captured if more than one job is running.
+fn run_jobs<T, F>(targets: Vec<(PathBuf, Repository, T)>, jobs: usize, f: F) -> Results
+  where T: Send + 'static,
+        F: Fn(&Path, &Repository, &T, &mut Output) -> Result<Outcome, GhqError>,
+        F: Send + Sync + 'static
+{
+  let capture = jobs > 1;
+  let job = move |(root, repo, arg): (PathBuf, Repository, T)| {
+    let mut out = if capture {
+      Output::Capture(String::new())
+    } else {
+      Output::Inherit
+    };
+    let result = f(&root, &repo, &arg, &mut out);
+    (repo.full_name(), result, out)
+  };
+  let done = |&(ref name, _, ref out): &(String, Result<Outcome, GhqError>, Output)| {
+    if let Some(captured) = out.captured() {
+      println!("==> {}", name);
+      print!("{}", captured);
+    }
+  };
+
+  let names: Vec<_> = targets.iter().map(|&(_, ref repo, _)| repo.full_name()).collect();
+  let results = jobs::run(targets, jobs, job, done);
+  names.into_iter()
+    .zip(results)
+    .map(|(name, r)| match r {
+      Ok((_, result, _)) => (name, result),
+      Err(msg) => (name, Err(GhqError::Panicked(msg))),
+    })
+    .collect()
+}
+
+fn clone_options(m: &ArgMatches) -> Result<CloneOptions, GhqError> {
+  let depth = match m.value_of("depth").map(|s| s.parse::<u32>()) {
+    Some(Ok(n)) if n > 0 => Some(n),
+    Some(_) => return Err("the depth must be a positive integer".into()),
+    None if m.is_present("shallow") => Some(1),
+    None => None,
+  };
+
+  Ok(CloneOptions {
+    depth: depth,
+    branch: m.value_of("branch").map(ToOwned::to_owned),
+    bare: m.is_present("bare"),
+    recursive: m.is_present("recursive"),
+  })
+}
+
+fn num_jobs(m: &ArgMatches) -> Result<usize, GhqError> {
+  match m.value_of("jobs").map(|s| s.parse::<usize>()) {
+    Some(Ok(n)) if n > 0 => Ok(n),
+    Some(_) => Err("the number of jobs must be a positive integer".into()),
+    None => Ok(1),
+  }
+}
+
+// show the summary of operations, and returns the exit code.
+fn report(results: &Results) -> i32 {
+  let mut failed = 0;
+
+  println!("");
+  for &(ref name, ref result) in results {
+    match *result {
+      Ok(outcome) => println!("{:<12}{}", outcome.to_string(), name),
+      Err(ref err) => {
+        failed += 1;
+        println!("{:<12}{}: {}", "failed", name, err.to_string());
+      }
+    }
+  }
+  println!("{} succeeded, {} failed", results.len() - failed, failed);
+
+  if failed > 0 { 1 } else { 0 }
+}
+
+fn shell() -> String {
+  if cfg!(windows) {
+    env::var("COMSPEC").unwrap_or("cmd.exe".to_owned())
+  } else {
+    env::var("SHELL").unwrap_or("/bin/sh".to_owned())
+  }
+}
+
 fn cli() -> App<'static, 'static> {
   App::new(env!("CARGO_PKG_NAME"))
     .about(env!("CARGO_PKG_DESCRIPTION"))
@@ -60,17 +389,210 @@ fn cli() -> App<'static, 'static> {
       .about("Clone remote repository into your working directory")
       .arg(Arg::with_name("query")
         .multiple(true)
-        .required(true)
+        .required_unless_one(&["from-file", "stdin"])
         .help("repository name or URL"))
-      .arg(Arg::with_name("root")
-        .long("root")
-        .help("root directory of cloned repository")))
+      .arg(Arg::with_name("from-file")
+        .long("from-file")
+        .takes_value(true)
+        .value_name("FILE")
+        .help("Read queries from the file, one per line with optional 'branch=', 'depth=' \
+               and 'vcs='"))
+      .arg(Arg::with_name("stdin")
+        .long("stdin")
+        .help("Read queries from the standard input, in the same form as '--from-file'"))
+      .arg(root_arg())
+      .arg(allow_unregistered_arg())
+      .arg(Arg::with_name("vcs")
+        .long("vcs")
+        .takes_value(true)
+        .possible_values(&["git", "hg", "svn", "darcs"])
+        .help("version control system of the remote repository"))
+      .arg(Arg::with_name("update")
+        .short("u")
+        .long("update")
+        .help("Update the local repository if it has already existed"))
+      .arg(Arg::with_name("ssh")
+        .short("p")
+        .long("ssh")
+        .help("Use SSH URLs for shorthand queries such as 'owner/repo'"))
+      .arg(Arg::with_name("shallow")
+        .long("shallow")
+        .conflicts_with("depth")
+        .help("Clone only the latest revision (same as '--depth 1')"))
+      .arg(Arg::with_name("depth")
+        .long("depth")
+        .takes_value(true)
+        .value_name("N")
+        .help("Clone only the latest N revisions"))
+      .arg(Arg::with_name("branch")
+        .short("b")
+        .long("branch")
+        .takes_value(true)
+        .help("Check out the branch instead of the default one"))
+      .arg(Arg::with_name("bare")
+        .long("bare")
+        .help("Clone without any working copy"))
+      .arg(Arg::with_name("recursive")
+        .long("recursive")
+        .help("Also clone submodules"))
+      .arg(jobs_arg()))
+    .subcommand(SubCommand::with_name("import")
+      .about("Move existing local repositories into the working directory")
+      .arg(Arg::with_name("dir")
+        .multiple(true)
+        .required(true)
+        .help("local repository to import"))
+      .arg(root_arg())
+      .arg(allow_unregistered_arg())
+      .arg(Arg::with_name("link")
+        .long("link")
+        .help("Create a symbolic link in the working directory instead of moving"))
+      .arg(Arg::with_name("dry-run")
+        .short("n")
+        .long("dry-run")
+        .help("Show what would be done without changing anything")))
+    .subcommand(SubCommand::with_name("export")
+      .about("Print a manifest of all repositories in the working directories")
+      .arg(Arg::with_name("format")
+        .long("format")
+        .takes_value(true)
+        .possible_values(&["toml", "json"])
+        .help("Format of the manifest (default: toml)")))
+    .subcommand(SubCommand::with_name("import-manifest")
+      .about("Clone all repositories recorded in a manifest")
+      .arg(Arg::with_name("file")
+        .required(true)
+        .help("manifest written by 'export', in TOML or JSON"))
+      .arg(root_arg())
+      .arg(allow_unregistered_arg())
+      .arg(Arg::with_name("checkout")
+        .long("checkout")
+        .help("Check out the recorded revisions after cloning"))
+      .arg(jobs_arg()))
+    .subcommand(SubCommand::with_name("update")
+      .about("Update local repositories in the working directories")
+      .arg(Arg::with_name("query")
+        .multiple(true)
+        .help("substring or regular expression matched against 'host/owner/repo'"))
+      .arg(jobs_arg()))
     .subcommand(SubCommand::with_name("list")
-      .about("List local repositories into the working directories"))
+      .about("List local repositories into the working directories")
+      .arg(Arg::with_name("query")
+        .help("substring or regular expression matched against 'host/owner/repo'"))
+      .arg(Arg::with_name("full-path")
+        .short("p")
+        .long("full-path")
+        .conflicts_with("unique")
+        .help("Print full paths of repositories"))
+      .arg(Arg::with_name("exact")
+        .short("e")
+        .long("exact")
+        .help("Match the query exactly with 'host/owner/repo', 'owner/repo' or 'repo'"))
+      .arg(Arg::with_name("unique")
+        .long("unique")
+        .help("Print the shortest unambiguous suffixes of repositories"))
+      .arg(Arg::with_name("vcs")
+        .long("vcs")
+        .takes_value(true)
+        .possible_values(&["git", "hg", "svn", "darcs"])
+        .help("Show only repositories of the version control system"))
+      .arg(Arg::with_name("url")
+        .long("url")
+        .conflicts_with("format")
+        .help("Print the remote URL of each repository after a tab"))
+      .arg(format_arg())
+      .arg(template_arg())
+      .arg(Arg::with_name("status")
+        .long("status")
+        .requires("format")
+        .help("Include the status of each repository in formatted output")))
+    .subcommand(SubCommand::with_name("check")
+      .about("Report local repositories whose remote URL disagrees with their location")
+      .arg(Arg::with_name("query")
+        .help("substring or regular expression matched against 'host/owner/repo'")))
+    .subcommand(SubCommand::with_name("status")
+      .about("Show local repositories which have changes or are out of sync")
+      .arg(Arg::with_name("query")
+        .help("substring or regular expression matched against 'host/owner/repo'"))
+      .arg(Arg::with_name("all")
+        .short("a")
+        .long("all")
+        .help("Show all repositories, including clean ones"))
+      .arg(Arg::with_name("state")
+        .long("state")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .possible_values(&["dirty", "untracked", "ahead", "behind", "detached", "in-progress",
+                           "stash", "submodules", "unknown"])
+        .conflicts_with("all")
+        .help("Show only repositories in the state"))
+      .arg(format_arg())
+      .arg(template_arg()))
+    .subcommand(SubCommand::with_name("look")
+      .about("Open a shell in a local repository")
+      .arg(Arg::with_name("query")
+        .required(true)
+        .help("'host/owner/repo', 'owner/repo' or the name of repository"))
+      .arg(Arg::with_name("print")
+        .short("p")
+        .long("print")
+        .help("Print the path of the repository instead of opening a shell")))
+    .subcommand(SubCommand::with_name("rm")
+      .about("Remove a local repository")
+      .arg(Arg::with_name("query")
+        .required(true)
+        .help("'host/owner/repo', 'owner/repo' or the name of repository"))
+      .arg(Arg::with_name("force")
+        .short("f")
+        .long("force")
+        .help("Remove even if the repository has changes which are not pushed")))
     .subcommand(SubCommand::with_name("root")
       .about("Show repositories's root")
       .arg(Arg::with_name("all")
         .short("a")
         .long("all")
-        .help("Show all roots")))
+        .help("Show all roots"))
+      .arg(format_arg())
+      .arg(template_arg()))
+}
+
+fn root_arg() -> Arg<'static, 'static> {
+  Arg::with_name("root")
+    .long("root")
+    .takes_value(true)
+    .value_name("path|index|name")
+    .help("root directory to place repositories")
+}
+
+fn allow_unregistered_arg() -> Arg<'static, 'static> {
+  Arg::with_name("allow-unregistered")
+    .long("allow-unregistered")
+    .requires("root")
+    .help("Allow to use a root directory which is not configured")
+}
+
+fn jobs_arg() -> Arg<'static, 'static> {
+  Arg::with_name("jobs")
+    .short("j")
+    .long("jobs")
+    .takes_value(true)
+    .value_name("N")
+    .help("The number of operations to run concurrently")
+}
+
+fn format_arg() -> Arg<'static, 'static> {
+  Arg::with_name("format")
+    .long("format")
+    .takes_value(true)
+    .possible_values(&["json", "tsv", "template"])
+    .help("Output format")
+}
+
+fn template_arg() -> Arg<'static, 'static> {
+  Arg::with_name("template")
+    .long("template")
+    .takes_value(true)
+    .requires("format")
+    .help("Template of each line with '{field}' placeholders, used with '--format template'")
 }
@@ -1,18 +1,158 @@
 extern crate ghq;
+extern crate clap;
 
 use std::env;
-use std::io::{self, Write};
-use ghq::vcs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use clap::{Arg, App};
+use ghq::config::Config;
+use ghq::error::GhqError;
+use ghq::prompt::{Prompt, PromptOptions};
+use ghq::vcs::{self, Status};
+use ghq::vcs::cache::{self, StatusCache};
 
 fn main() {
+  if let Err(err) = _main() {
+    writeln!(&mut io::stderr(), "Error: {}", err.to_string()).unwrap();
+    std::process::exit(1);
+  }
+}
+
+fn _main() -> Result<(), GhqError> {
   // ensure that all outputs are English.
   env::set_var("LANGUAGE", "en_US.UTF-8");
   env::set_var("LANG", "en_US.UTF-8");
 
-  let fallback = env::args().skip(1).next().map(|s| s.trim() == "--fallback").unwrap_or(false);
+  let matches = App::new("vcs_info")
+    .about("Print the status of the current repository for shell prompts")
+    .arg(Arg::from_usage("--symbols=[NAME] 'Name of the symbol set, e.g. unicode or ascii'")
+      .conflicts_with("fallback"))
+    .arg(Arg::from_usage("--fallback 'Use the ASCII symbols (same as --symbols=ascii)'"))
+    .arg(Arg::from_usage("--theme=[NAME] 'Name of the theme to colour the prompt'"))
+    .arg(Arg::from_usage("--shell=[SHELL] 'Wrap escape sequences for the shell'")
+      .possible_values(&["bash", "zsh", "fish", "plain"]))
+    .arg(Arg::from_usage("--timeout=[MS] 'Show the branch only if the status takes longer'"))
+    .arg(Arg::from_usage("--cache 'Reuse the status while the repository is unchanged'"))
+    .arg(Arg::with_name("status-worker").long("status-worker").hidden(true))
+    .get_matches();
+
+  let opts = PromptOptions {
+    symbols: if matches.is_present("fallback") {
+      Some("ascii".to_owned())
+    } else {
+      matches.value_of("symbols").map(ToOwned::to_owned)
+    },
+    theme: matches.value_of("theme").map(ToOwned::to_owned),
+    shell: match matches.value_of("shell") {
+      Some(shell) => shell.parse()?,
+      None => Default::default(),
+    },
+  };
+
+  // the rest of the config (e.g. roots with unset variables) must not break the prompt.
+  let prompt_config = Config::load_prompt()?.unwrap_or_default();
+  let prompt = Prompt::new(Some(&prompt_config), &opts)?;
+
+  let timeout = match matches.value_of("timeout").map(|s| s.parse::<u64>()) {
+    Some(Ok(ms)) => Some(ms),
+    Some(Err(_)) => return Err("the timeout must be a number of milliseconds".into()),
+    None => prompt_config.timeout,
+  };
+  let use_cache = matches.is_present("cache") || prompt_config.cache.unwrap_or(false);
+  // the directory of the cache also holds the locks of workers, even if the cache is disabled.
+  let ttl = prompt_config.cache_ttl.unwrap_or(cache::DEFAULT_TTL);
+  let store = StatusCache::default_dir().map(|dir| StatusCache::new(dir, ttl));
+  let cache = if use_cache { store.as_ref() } else { None };
+
+  let cwd = env::current_dir()?;
+  let root = match vcs::repository_root(&cwd) {
+    Some(root) => root.to_owned(),
+    None => return Ok(()),
+  };
+
+  if matches.is_present("status-worker") {
+    return work(&root, store.as_ref(), cache);
+  }
+
+  if let Some(status) = cache.and_then(|cache| cache.load(&root)) {
+    io::stdout().write_all(prompt.render(&status).as_bytes())?;
+    return Ok(());
+  }
+
+  let status = match timeout {
+    Some(ms) => {
+      match status_within(&root, Duration::from_millis(ms), use_cache) {
+        Some(status) => Ok(Some(status)),
+        None => {
+          if let Ok(Some(status)) = vcs::partial_status(&root) {
+            io::stdout().write_all(prompt.render_partial(&status).as_bytes())?;
+          }
+          return Ok(());
+        }
+      }
+    }
+    None => vcs::status_of(&root),
+  };
+
+  if let Ok(Some(status)) = status {
+    if let Some(cache) = cache {
+      // failing to cache is not worth breaking the prompt.
+      let _ = cache.store(&root, &status);
+    }
+    io::stdout().write_all(prompt.render(&status).as_bytes())?;
+  }
+  Ok(())
+}
+
+// collect the status in a worker process, and give up after `timeout`. the worker is left to
+// finish then, and caches the status for the next prompt if the cache is enabled. it doesn't
+// inherit the output of the prompt, so that the shell doesn't wait for it either.
+fn status_within(root: &Path, timeout: Duration, use_cache: bool) -> Option<Status> {
+  let mut command = Command::new(env::current_exe().ok()?);
+  command.arg("--status-worker")
+    .current_dir(root)
+    .stdin(Stdio::null())
+    .stdout(Stdio::piped())
+    .stderr(Stdio::null());
+  if use_cache {
+    command.arg("--cache");
+  }
+  let mut child = command.spawn().ok()?;
+
+  let mut stdout = child.stdout.take()?;
+  let (tx, rx) = mpsc::channel();
+  thread::spawn(move || {
+    let mut content = String::new();
+    let _ = stdout.read_to_string(&mut content);
+    let _ = tx.send(content);
+  });
+  let content = rx.recv_timeout(timeout).ok()?;
+  let _ = child.wait();
+  cache::decode_status(&content)
+}
 
-  match vcs::current_status(&env::current_dir().unwrap()) {
-    Ok(Some(s)) => io::stdout().write_all(s.prompt(fallback).as_bytes()).unwrap(),
-    _ => (),
+// the worker of `status_within`. it holds a lock while running, so that slow repositories don't
+// pile up workers which contend for `index.lock`, and caches the status before printing it since
+// the prompt may have given up on it.
+fn work(root: &Path,
+        store: Option<&StatusCache>,
+        cache: Option<&StatusCache>)
+        -> Result<(), GhqError> {
+  let _lock = match store.map(|store| store.lock(root)) {
+    Some(Ok(None)) => return Err("another worker is collecting the status".into()),
+    Some(Ok(lock)) => lock,
+    _ => None,
+  };
+  if let Some(status) = vcs::status_of(root)? {
+    if let Some(cache) = cache {
+      let _ = cache.store(root, &status);
+    }
+    // the prompt may have exited already.
+    let _ = io::stdout().write_all(cache::encode_status(&status)?.as_bytes());
   }
+  Ok(())
 }
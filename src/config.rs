@@ -1,10 +1,15 @@
 use std::borrow::Cow;
+use std::collections::BTreeMap;
 use std::env::VarError;
 use std::fs::File;
 use std::io::{self, Read};
+use std::ops::{Deref, DerefMut};
 use std::path::PathBuf;
+use rustc_serialize::{Decodable, Decoder};
 use toml;
 use shellexpand::{self, LookupError};
+use host::HostConfig;
+use prompt::PromptConfig;
 
 
 #[cfg_attr(rustfmt, rustfmt_skip)]
@@ -17,30 +22,128 @@ const CANDIDATES: &'static [&'static str] = &[
 
 #[derive(RustcDecodable)]
 pub struct Config {
-  pub roots: Vec<String>,
+  pub roots: Roots,
+  // VCS used by each host, e.g. `"hg.mozilla.org" = "hg"`
+  pub vcs: Option<BTreeMap<String, String>>,
+  // expand shorthand queries (`owner/repo`) to SSH URLs for all hosts
+  pub ssh: Option<bool>,
+  // hosts whose shorthand queries are expanded to SSH URLs
+  pub ssh_hosts: Option<Vec<String>>,
+  // rules for each host, see `host::HostConfig`
+  pub hosts: Option<BTreeMap<String, HostConfig>>,
+  // templates of the prompt of `vcs_info`, see `prompt::PromptConfig`
+  pub prompt: Option<PromptConfig>,
 }
 
 impl Default for Config {
   fn default() -> Config {
-    Config { roots: vec!["~/.ghq".to_owned()] }
+    Config {
+      roots: Roots(vec![Root {
+                         name: None,
+                         path: "~/.ghq".to_owned(),
+                       }]),
+      vcs: None,
+      ssh: None,
+      ssh_hosts: None,
+      hosts: None,
+      prompt: None,
+    }
   }
 }
 
+// the `[prompt]` table alone, so that `vcs_info` doesn't depend on the rest of the config.
+#[derive(RustcDecodable)]
+struct PromptOnly {
+  prompt: Option<PromptConfig>,
+}
+
 impl Config {
   pub fn load() -> io::Result<Config> {
     let content = read_file_if_exists(CANDIDATES)?;
     let mut config: Config = content.and_then(|s| toml::decode_str(&s)).unwrap_or_default();
+    config.expand_roots()?;
+    Ok(config)
+  }
 
-    for i in 0..(config.roots.len()) {
-      config.roots[i] = expand_full(&config.roots[i]).unwrap();
+  // read only the `[prompt]` table, without expanding the roots.
+  pub fn load_prompt() -> io::Result<Option<PromptConfig>> {
+    let content = read_file_if_exists(CANDIDATES)?;
+    Ok(content.and_then(|s| toml::decode_str::<PromptOnly>(&s)).and_then(|c| c.prompt))
+  }
+
+  fn expand_roots(&mut self) -> io::Result<()> {
+    for root in self.roots.iter_mut() {
+      root.path = expand_full(&root.path).map_err(|err| {
+          io::Error::new(io::ErrorKind::InvalidData,
+                         format!("failed to expand the root `{}`: {}", root.path, err))
+        })?;
     }
+    Ok(())
+  }
+}
 
-    Ok(config)
+
+pub struct Root {
+  pub name: Option<String>,
+  pub path: String,
+}
+
+// Root directories of the workspace, written in either form of:
+//
+// ```toml
+// roots = ["~/.ghq", "~/go/src"]
+//
+// [roots.work]
+// path = "~/work"
+// default = true
+// ```
+pub struct Roots(pub Vec<Root>);
+
+impl Deref for Roots {
+  type Target = Vec<Root>;
+  fn deref(&self) -> &Vec<Root> {
+    &self.0
   }
 }
 
+impl DerefMut for Roots {
+  fn deref_mut(&mut self) -> &mut Vec<Root> {
+    &mut self.0
+  }
+}
 
-fn expand_full(s: &str) -> Result<String, LookupError<VarError>> {
+#[derive(RustcDecodable)]
+struct NamedRoot {
+  path: String,
+  default: Option<bool>,
+}
+
+impl Decodable for Roots {
+  fn decode<D: Decoder>(d: &mut D) -> Result<Roots, D::Error> {
+    if let Ok(paths) = Vec::<String>::decode(d) {
+      let roots = paths.into_iter().map(|path| Root { name: None, path: path }).collect();
+      return Ok(Roots(roots));
+    }
+
+    // the default root comes first, and the others are sorted by their names.
+    let named = BTreeMap::<String, NamedRoot>::decode(d)?;
+    let (default, others): (Vec<_>, Vec<_>) = named.into_iter()
+      .partition(|&(_, ref root)| root.default.unwrap_or(false));
+    let roots = default.into_iter()
+      .chain(others)
+      .map(|(name, root)| {
+        Root {
+          name: Some(name),
+          path: root.path,
+        }
+      })
+      .collect();
+    Ok(Roots(roots))
+  }
+}
+
+
+pub fn expand_full(s: &str) -> Result<String, LookupError<VarError>> {
   shellexpand::full(s).map(Cow::into_owned)
 }
 
@@ -62,3 +165,54 @@ fn read_file_if_exists(paths: &[&str]) -> io::Result<Option<String>> {
     None => Ok(None),
   }
 }
+
+
+#[cfg(test)]
+mod test_roots {
+  use toml;
+  use super::{Config, PromptOnly};
+
+  #[test]
+  fn array() {
+    let config: Config = toml::decode_str(r#"roots = ["~/.ghq", "~/go/src"]"#).unwrap();
+    let roots: Vec<_> = config.roots.iter().map(|r| (r.name.clone(), r.path.as_str())).collect();
+    assert_eq!(roots, vec![(None, "~/.ghq"), (None, "~/go/src")]);
+  }
+
+  #[test]
+  fn named() {
+    let config: Config = toml::decode_str(r#"
+      [roots.oss]
+      path = "~/.ghq"
+      [roots.work]
+      path = "~/work"
+      default = true
+    "#)
+      .unwrap();
+    let roots: Vec<_> = config.roots
+      .iter()
+      .map(|r| (r.name.as_ref().unwrap().as_str(), r.path.as_str()))
+      .collect();
+    assert_eq!(roots, vec![("work", "~/work"), ("oss", "~/.ghq")]);
+  }
+
+  #[test]
+  fn unset_variable() {
+    let mut config: Config = toml::decode_str(r#"
+      roots = ["$GHQRS_TEST_UNSET/src"]
+      [prompt]
+      timeout = 100
+    "#)
+      .unwrap();
+    assert!(config.expand_roots().is_err());
+
+    // the prompt doesn't care about the roots.
+    let config: PromptOnly = toml::decode_str(r#"
+      roots = ["$GHQRS_TEST_UNSET/src"]
+      [prompt]
+      timeout = 100
+    "#)
+      .unwrap();
+    assert_eq!(config.prompt.unwrap().timeout, Some(100));
+  }
+}
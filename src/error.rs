@@ -8,6 +8,19 @@ pub enum GhqError {
   IO(io::Error),
   StripPrefix(StripPrefixError),
   UrlParse(ParseError),
+  NotFound(String),
+  Ambiguous(String, Vec<String>),
+  UnregisteredRoot(String),
+  // the repository has changes which would be lost, with descriptions of them
+  Unsafe(String, Vec<String>),
+  // the name of symbol set is neither built-in nor configured
+  UnknownSymbolSet(String),
+  UnknownTheme(String),
+  // the style of a theme can't be parsed
+  InvalidStyle(String),
+  UnknownShell(String),
+  // a job for the repository has panicked, with the message of the panic
+  Panicked(String),
   Other(&'static str),
 }
 
@@ -17,6 +30,32 @@ impl GhqError {
       GhqError::IO(ref err) => err.to_string(),
       GhqError::StripPrefix(ref err) => err.to_string(),
       GhqError::UrlParse(ref err) => err.to_string(),
+      GhqError::NotFound(ref query) => format!("no repository matches '{}'", query),
+      GhqError::Ambiguous(ref query, ref candidates) => {
+        format!("'{}' is ambiguous. candidates are:\n  {}",
+                query,
+                candidates.join("\n  "))
+      }
+      GhqError::UnregisteredRoot(ref root) => {
+        format!("'{}' is not a configured root (use --allow-unregistered to use it anyway)",
+                root)
+      }
+      GhqError::Unsafe(ref repo, ref reasons) => {
+        format!("'{}' has {} (use --force to remove it anyway)",
+                repo,
+                reasons.join(", "))
+      }
+      GhqError::UnknownSymbolSet(ref name) => {
+        format!("unknown symbol set '{}' (built-in sets are 'unicode' and 'ascii')", name)
+      }
+      GhqError::UnknownTheme(ref name) => {
+        format!("unknown theme '{}' (built-in themes are 'none' and 'default')", name)
+      }
+      GhqError::InvalidStyle(ref style) => format!("invalid style '{}'", style),
+      GhqError::UnknownShell(ref name) => {
+        format!("unknown shell '{}' (supported shells are bash, zsh, fish and plain)", name)
+      }
+      GhqError::Panicked(ref msg) => format!("panicked: {}", msg),
       GhqError::Other(ref err) => err.to_string(),
     }
   }
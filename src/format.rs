@@ -0,0 +1,275 @@
+use std::path::Path;
+use rustc_serialize::Encodable;
+use rustc_serialize::json;
+use error::GhqError;
+use repository::Repository;
+use vcs::{self, State, VCS};
+
+
+pub enum Format {
+  Json,
+  Tsv,
+  Template(String),
+}
+
+impl Format {
+  pub fn new(name: &str, template: Option<&str>) -> Result<Format, GhqError> {
+    match name {
+      "json" => Ok(Format::Json),
+      "tsv" => Ok(Format::Tsv),
+      "template" => {
+        template.map(|t| Format::Template(t.to_owned()))
+          .ok_or("the template is not specified".into())
+      }
+      _ => Err("unsupported output format".into()),
+    }
+  }
+
+  pub fn render<R: Record>(&self, records: &[R]) -> String {
+    match *self {
+      Format::Json => json::as_pretty_json(&records).to_string(),
+      Format::Tsv => {
+        records.iter()
+          .map(|r| {
+            r.columns()
+              .iter()
+              .map(|&name| r.field(name).unwrap_or_default().replace("\t", " ").replace("\n", " "))
+              .collect::<Vec<_>>()
+              .join("\t")
+          })
+          .collect::<Vec<_>>()
+          .join("\n")
+      }
+      Format::Template(ref t) => {
+        records.iter().map(|r| expand(t, r)).collect::<Vec<_>>().join("\n")
+      }
+    }
+  }
+}
+
+
+pub trait Record: Encodable {
+  // names of fields, in order of TSV columns.
+  fn columns(&self) -> Vec<&'static str>;
+
+  fn field(&self, name: &str) -> Option<String>;
+}
+
+
+#[derive(RustcEncodable)]
+pub struct RepoRecord {
+  pub root: String,
+  pub host: String,
+  pub owner: String,
+  pub name: String,
+  pub path: String,
+  pub vcs: VCS,
+  pub url: Option<String>,
+  pub status: Option<vcs::Status>,
+}
+
+impl RepoRecord {
+  pub fn new(repo: &Repository, root: &Path, with_status: bool) -> RepoRecord {
+    let path = repo.local_path(root);
+    let status = if with_status {
+      vcs::status_of(&path).ok().and_then(|s| s)
+    } else {
+      None
+    };
+
+    RepoRecord {
+      root: root.to_string_lossy().replace("\\", "/"),
+      host: repo.host().to_owned(),
+      owner: repo.owner().to_owned(),
+      name: repo.name().to_owned(),
+      path: path.to_string_lossy().replace("\\", "/"),
+      vcs: repo.vcs(),
+      url: repo.remote_url(root).map(|url| url.as_str().to_owned()),
+      status: status,
+    }
+  }
+}
+
+impl Record for RepoRecord {
+  fn columns(&self) -> Vec<&'static str> {
+    // status columns are always emitted (and left empty if not collected) to keep the layout.
+    vec!["root", "host", "owner", "name", "path", "vcs", "url", "branch", "ahead", "behind",
+         "changes", "untracked", "stash"]
+  }
+
+  fn field(&self, name: &str) -> Option<String> {
+    match name {
+      "root" => Some(self.root.clone()),
+      "host" => Some(self.host.clone()),
+      "owner" => Some(self.owner.clone()),
+      "name" => Some(self.name.clone()),
+      "path" => Some(self.path.clone()),
+      "vcs" => Some(self.vcs.to_string()),
+      "url" => Some(self.url.clone().unwrap_or_default()),
+      _ => {
+        self.status.as_ref().and_then(|s| match name {
+          "branch" => Some(s.branch().to_owned()),
+          "ahead" => Some(s.ahead_by().to_string()),
+          "behind" => Some(s.behind_by().to_string()),
+          "changes" => Some(s.changes().to_string()),
+          "untracked" => Some(s.untracked().to_string()),
+          "stash" => Some(s.stash_count().to_string()),
+          _ => None,
+        })
+      }
+    }
+  }
+}
+
+
+#[derive(RustcEncodable)]
+pub struct RootRecord {
+  pub name: Option<String>,
+  pub path: String,
+  pub default: bool,
+}
+
+impl Record for RootRecord {
+  fn columns(&self) -> Vec<&'static str> {
+    vec!["name", "path", "default"]
+  }
+
+  fn field(&self, name: &str) -> Option<String> {
+    match name {
+      "name" => Some(self.name.clone().unwrap_or_default()),
+      "path" => Some(self.path.clone()),
+      "default" => Some(self.default.to_string()),
+      _ => None,
+    }
+  }
+}
+
+
+#[derive(RustcEncodable)]
+pub struct StatusRecord {
+  pub name: String,
+  pub path: String,
+  pub vcs: VCS,
+  pub branch: Option<String>,
+  pub states: Vec<State>,
+  pub status: Option<vcs::Status>,
+  // why the status is unknown
+  pub error: Option<String>,
+}
+
+impl StatusRecord {
+  pub fn new(repo: &Repository, root: &Path) -> StatusRecord {
+    let path = repo.local_path(root);
+    let (status, error) = match vcs::status_of(&path) {
+      Ok(Some(status)) => (Some(status), None),
+      Ok(None) => (None, Some(format!("no status available for {}", repo.vcs()))),
+      Err(err) => (None, Some(err.to_string())),
+    };
+
+    StatusRecord {
+      name: repo.full_name(),
+      path: path.to_string_lossy().replace("\\", "/"),
+      vcs: repo.vcs(),
+      branch: status.as_ref().map(|s| s.branch().to_owned()),
+      states: status.as_ref().map(|s| s.states()).unwrap_or(vec![State::Unknown]),
+      status: status,
+      error: error,
+    }
+  }
+
+  // e.g. `dirty,ahead`
+  pub fn states_str(&self) -> String {
+    self.states.iter().map(ToString::to_string).collect::<Vec<_>>().join(",")
+  }
+}
+
+impl Record for StatusRecord {
+  fn columns(&self) -> Vec<&'static str> {
+    vec!["name", "path", "vcs", "branch", "states"]
+  }
+
+  fn field(&self, name: &str) -> Option<String> {
+    match name {
+      "name" => Some(self.name.clone()),
+      "path" => Some(self.path.clone()),
+      "vcs" => Some(self.vcs.to_string()),
+      "branch" => Some(self.branch.clone().unwrap_or_default()),
+      "states" => Some(self.states_str()),
+      "error" => Some(self.error.clone().unwrap_or_default()),
+      _ => None,
+    }
+  }
+}
+
+
+// replace `{name}` in the template with fields of the record.
+// unknown placeholders are left as they are.
+fn expand<R: Record>(template: &str, record: &R) -> String {
+  let mut ret = String::new();
+  let mut rest = template;
+
+  while let Some(start) = rest.find("{") {
+    ret += &rest[..start];
+    rest = &rest[start..];
+    match rest.find("}") {
+      Some(end) => {
+        match record.field(&rest[1..end]) {
+          Some(value) => ret += &value,
+          None => ret += &rest[..end + 1],
+        }
+        rest = &rest[end + 1..];
+      }
+      None => break,
+    }
+  }
+  ret += rest;
+
+  ret
+}
+
+
+#[cfg(test)]
+mod test_format {
+  use std::path::Path;
+  use super::{Format, RepoRecord, StatusRecord};
+  use host::Hosts;
+  use repository::Repository;
+
+  fn records() -> Vec<RepoRecord> {
+    let repo = Repository::from_local("github.com/hoge/fuga", &Hosts::default()).unwrap();
+    vec![RepoRecord::new(&repo, Path::new("/ghq"), false)]
+  }
+
+  #[test]
+  fn tsv() {
+    assert_eq!(Format::Tsv.render(&records()),
+               "/ghq\tgithub.com\thoge\tfuga\t/ghq/github.com/hoge/fuga\tgit\t\t\t\t\t\t\t");
+  }
+
+  #[test]
+  fn template() {
+    let format = Format::new("template", Some("{host}:{owner}/{name} ({vcs}) {unknown}")).unwrap();
+    assert_eq!(format.render(&records()), "github.com:hoge/fuga (git) {unknown}");
+  }
+
+  #[test]
+  fn json() {
+    let out = Format::Json.render(&records());
+    assert!(out.contains("\"host\": \"github.com\""));
+    assert!(out.contains("\"url\": null"));
+    assert!(out.contains("\"status\": null"));
+  }
+
+  #[test]
+  fn unknown_status() {
+    let repo = Repository::from_local("github.com/hoge/fuga", &Hosts::default()).unwrap();
+    let records = vec![StatusRecord::new(&repo, Path::new("/nonexistent/ghq"))];
+    assert_eq!(records[0].states_str(), "unknown");
+    assert_eq!(Format::Tsv.render(&records),
+               "github.com/hoge/fuga\t/nonexistent/ghq/github.com/hoge/fuga\tgit\t\tunknown");
+
+    let out = Format::Json.render(&records);
+    assert!(out.contains("\"states\": [\n      \"unknown\"\n    ]"), "{}", out);
+    assert!(out.contains("\"error\": \"no status available for git\""));
+  }
+}
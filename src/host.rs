@@ -0,0 +1,287 @@
+use std::collections::BTreeMap;
+use rustc_serialize::{Decodable, Decoder};
+use url::Url;
+use config::Config;
+use error::GhqError;
+use vcs::VCS;
+
+
+// The number of path components which identify a repository on the host.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Depth {
+  Fixed(usize),
+  // arbitrary depth, up to the directory which contains the VCS directory (e.g. `.git`).
+  // used for hosts with nested groups such as GitLab.
+  UntilVcs,
+}
+
+// `depth = 2` or `depth = "until-git"`
+impl Decodable for Depth {
+  fn decode<D: Decoder>(d: &mut D) -> Result<Depth, D::Error> {
+    if let Ok(n) = d.read_usize() {
+      return Ok(Depth::Fixed(n));
+    }
+    match d.read_str()?.as_str() {
+      "until-git" | "until-vcs" => Ok(Depth::UntilVcs),
+      s => Err(d.error(&format!("invalid depth: {}", s))),
+    }
+  }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct HostRule {
+  pub depth: Depth,
+  // "https", "http", "ssh" or "git"
+  pub scheme: String,
+  // the user of SSH URLs
+  pub user: Option<String>,
+  pub vcs: Option<VCS>,
+  // template of remote URL, with placeholders `{scheme}`, `{user}`, `{host}` and `{path}`
+  pub url: Option<String>,
+}
+
+impl Default for HostRule {
+  fn default() -> HostRule {
+    HostRule {
+      depth: Depth::Fixed(2),
+      scheme: "https".to_owned(),
+      user: None,
+      vcs: None,
+      url: None,
+    }
+  }
+}
+
+impl HostRule {
+  fn new(depth: Depth, vcs: VCS, url: Option<&str>) -> HostRule {
+    HostRule {
+      depth: depth,
+      vcs: Some(vcs),
+      url: url.map(ToOwned::to_owned),
+      ..HostRule::default()
+    }
+  }
+
+  // check if the number of path components is acceptable as a repository.
+  pub fn accepts(&self, depth: usize) -> bool {
+    match self.depth {
+      Depth::Fixed(n) => depth == n,
+      Depth::UntilVcs => depth >= 1,
+    }
+  }
+
+  // the number of leading path components which are used in the remote URL.
+  pub fn url_depth(&self, depth: usize) -> usize {
+    match self.depth {
+      Depth::Fixed(n) => n,
+      Depth::UntilVcs => depth,
+    }
+  }
+}
+
+
+// An entry of `[hosts]` in the configuration file.
+//
+// ```toml
+// [hosts."gitlab.example.com"]
+// depth = "until-git"
+// scheme = "ssh"
+// user = "git"
+// vcs = "git"
+// url = "{scheme}://{user}{host}:2222/{path}.git"
+// ```
+#[derive(RustcDecodable)]
+pub struct HostConfig {
+  pub depth: Option<Depth>,
+  pub scheme: Option<String>,
+  pub user: Option<String>,
+  pub vcs: Option<String>,
+  pub url: Option<String>,
+}
+
+
+// The registry of rules for each host.
+pub struct Hosts {
+  rules: BTreeMap<String, HostRule>,
+  default: HostRule,
+  ssh: bool,
+}
+
+impl Default for Hosts {
+  fn default() -> Hosts {
+    let mut rules = BTreeMap::new();
+    rules.insert("github.com".to_owned(),
+                 HostRule::new(Depth::Fixed(2), VCS::Git, None));
+    rules.insert("gist.github.com".to_owned(),
+                 HostRule::new(Depth::Fixed(1), VCS::Git, None));
+    rules.insert("gitlab.com".to_owned(),
+                 HostRule::new(Depth::UntilVcs, VCS::Git, None));
+    rules.insert("bitbucket.org".to_owned(),
+                 HostRule::new(Depth::Fixed(2), VCS::Git, None));
+    rules.insert("codeberg.org".to_owned(),
+                 HostRule::new(Depth::Fixed(2), VCS::Git, None));
+    rules.insert("git.sr.ht".to_owned(),
+                 HostRule::new(Depth::Fixed(2),
+                               VCS::Git,
+                               Some("{scheme}://{user}{host}/{path}")));
+    rules.insert("hg.sr.ht".to_owned(),
+                 HostRule::new(Depth::Fixed(2),
+                               VCS::Hg,
+                               Some("{scheme}://{user}{host}/{path}")));
+    // clones from `file://` URLs, which keep the whole path of the upstream. the VCS is
+    // probed from the upstream.
+    rules.insert("localhost".to_owned(),
+                 HostRule {
+                   depth: Depth::UntilVcs,
+                   ..HostRule::default()
+                 });
+
+    Hosts {
+      rules: rules,
+      default: HostRule::default(),
+      ssh: false,
+    }
+  }
+}
+
+impl Hosts {
+  // built-in rules, overridden by the configuration.
+  pub fn from_config(config: &Config) -> Hosts {
+    let mut hosts = Hosts { ssh: config.ssh.unwrap_or(false), ..Hosts::default() };
+
+    for (host, vcs) in config.vcs.iter().flat_map(|m| m.iter()) {
+      hosts.rule_mut(host).vcs = vcs.parse().ok();
+    }
+    for host in config.ssh_hosts.iter().flat_map(|h| h.iter()) {
+      hosts.rule_mut(host).scheme = "ssh".to_owned();
+    }
+
+    for (host, c) in config.hosts.iter().flat_map(|m| m.iter()) {
+      let rule = hosts.rule_mut(host);
+      if let Some(depth) = c.depth {
+        rule.depth = depth;
+      }
+      if let Some(ref scheme) = c.scheme {
+        rule.scheme = scheme.clone();
+      }
+      if let Some(ref user) = c.user {
+        rule.user = Some(user.clone());
+      }
+      if let Some(ref vcs) = c.vcs {
+        rule.vcs = vcs.parse().ok();
+      }
+      if let Some(ref url) = c.url {
+        rule.url = Some(url.clone());
+      }
+    }
+
+    hosts
+  }
+
+  pub fn get(&self, host: &str) -> &HostRule {
+    self.rules.get(host).unwrap_or(&self.default)
+  }
+
+  // check if the host is either built-in or configured.
+  pub fn contains(&self, host: &str) -> bool {
+    self.rules.contains_key(host)
+  }
+
+  fn rule_mut(&mut self, host: &str) -> &mut HostRule {
+    let default = self.default.clone();
+    self.rules.entry(host.to_owned()).or_insert(default)
+  }
+
+  // build the remote URL of a repository from the host and the path of the repository.
+  pub fn remote_url(&self, host: &str, path: &str, vcs: Option<VCS>) -> Result<Url, GhqError> {
+    let rule = self.get(host);
+    let vcs = vcs.or(rule.vcs).unwrap_or(VCS::Git);
+    let scheme = if self.ssh { "ssh" } else { rule.scheme.as_str() };
+
+    let user = match (scheme, rule.user.as_ref()) {
+      (_, Some(user)) => format!("{}@", user),
+      ("ssh", None) if vcs == VCS::Hg => "hg@".to_owned(),
+      ("ssh", None) => "git@".to_owned(),
+      _ => String::new(),
+    };
+    let template = match rule.url {
+      Some(ref url) => url.as_str(),
+      None if vcs == VCS::Git => "{scheme}://{user}{host}/{path}.git",
+      None => "{scheme}://{user}{host}/{path}",
+    };
+
+    let url = template.replace("{scheme}", scheme)
+      .replace("{user}", &user)
+      .replace("{host}", host)
+      .replace("{path}", path);
+    Url::parse(&url).map_err(Into::into)
+  }
+}
+
+
+#[cfg(test)]
+mod test_hosts {
+  use toml;
+  use super::{Depth, Hosts};
+  use config::Config;
+  use vcs::VCS;
+
+  #[test]
+  fn builtin() {
+    let hosts = Hosts::default();
+    assert_eq!(hosts.get("github.com").depth, Depth::Fixed(2));
+    assert_eq!(hosts.get("gist.github.com").depth, Depth::Fixed(1));
+    assert_eq!(hosts.get("gitlab.com").depth, Depth::UntilVcs);
+    assert_eq!(hosts.get("hg.sr.ht").vcs, Some(VCS::Hg));
+    assert_eq!(hosts.get("localhost").depth, Depth::UntilVcs);
+    assert_eq!(hosts.get("localhost").vcs, None);
+    assert_eq!(hosts.get("unknown.example.com").depth, Depth::Fixed(2));
+  }
+
+  #[test]
+  fn from_config() {
+    let config: Config = toml::decode_str(r#"
+      roots = ["~/.ghq"]
+      ssh_hosts = ["github.com"]
+
+      [vcs]
+      "hg.mozilla.org" = "hg"
+
+      [hosts."gitlab.example.com"]
+      depth = "until-git"
+      scheme = "ssh"
+      user = "gitlab"
+
+      [hosts."git.example.com"]
+      depth = 3
+      url = "https://{host}/scm/{path}.git"
+    "#)
+      .unwrap();
+    let hosts = Hosts::from_config(&config);
+
+    assert_eq!(hosts.get("hg.mozilla.org").vcs, Some(VCS::Hg));
+    assert_eq!(hosts.get("github.com").scheme, "ssh");
+    assert_eq!(hosts.get("gitlab.example.com").depth, Depth::UntilVcs);
+    assert_eq!(hosts.get("git.example.com").depth, Depth::Fixed(3));
+
+    assert_eq!(hosts.remote_url("gitlab.example.com", "a/b/c", None).unwrap().as_str(),
+               "ssh://gitlab@gitlab.example.com/a/b/c.git");
+    assert_eq!(hosts.remote_url("git.example.com", "a/b/c", None).unwrap().as_str(),
+               "https://git.example.com/scm/a/b/c.git");
+    assert_eq!(hosts.remote_url("hg.mozilla.org", "mozilla-central", None).unwrap().as_str(),
+               "https://hg.mozilla.org/mozilla-central");
+  }
+
+  #[test]
+  fn global_ssh() {
+    let config = Config { ssh: Some(true), ..Config::default() };
+    let hosts = Hosts::from_config(&config);
+    assert_eq!(hosts.remote_url("hg.sr.ht", "~hoge/fuga", None).unwrap().as_str(),
+               "ssh://hg@hg.sr.ht/~hoge/fuga");
+
+    let hosts = Hosts::from_config(&Config::default());
+    assert_eq!(hosts.remote_url("github.com", "hoge/fuga", None).unwrap().as_str(),
+               "https://github.com/hoge/fuga.git");
+  }
+}
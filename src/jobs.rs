@@ -0,0 +1,114 @@
+use std::cmp;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc;
+use std::thread;
+
+
+// Run `f` for each item on at most `jobs` worker threads.
+//
+// `done` is called on the current thread each time a job is finished,
+// and the results are returned in the original order of `items`.
+// a job which has panicked gives the message of the panic instead, without stopping the others.
+pub fn run<T, R, F, D>(items: Vec<T>, jobs: usize, f: F, mut done: D) -> Vec<Result<R, String>>
+  where T: Send + 'static,
+        R: Send + 'static,
+        F: Fn(T) -> R + Send + Sync + 'static,
+        D: FnMut(&R)
+{
+  let len = items.len();
+  let queue = Arc::new(Mutex::new(items.into_iter().enumerate()));
+  let f = Arc::new(f);
+  let (tx, rx) = mpsc::channel();
+
+  let workers: Vec<_> = (0..cmp::max(1, cmp::min(jobs, len)))
+    .map(|_| {
+      let queue = queue.clone();
+      let f = f.clone();
+      let tx = tx.clone();
+      thread::spawn(move || loop {
+        let item = queue.lock().unwrap().next();
+        match item {
+          Some((i, item)) => {
+            let result = panic::catch_unwind(AssertUnwindSafe(|| f(item))).map_err(|payload| {
+              // `panic!` gives either `&str` or `String`, unless it is called with another value.
+              match payload.downcast::<String>() {
+                Ok(msg) => *msg,
+                Err(payload) => {
+                  payload.downcast_ref::<&str>().map_or("unknown".to_owned(), |s| s.to_string())
+                }
+              }
+            });
+            if tx.send((i, result)).is_err() {
+              break;
+            }
+          }
+          None => break,
+        }
+      })
+    })
+    .collect();
+  drop(tx);
+
+  let mut results: Vec<Option<Result<R, String>>> = (0..len).map(|_| None).collect();
+  for (i, result) in rx {
+    if let Ok(ref result) = result {
+      done(result);
+    }
+    results[i] = Some(result);
+  }
+  for worker in workers {
+    let _ = worker.join();
+  }
+
+  results.into_iter().map(|r| r.unwrap_or_else(|| Err("the job was lost".to_owned()))).collect()
+}
+
+
+#[cfg(test)]
+mod test_run {
+  use std::sync::Arc;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::thread;
+  use std::time::Duration;
+  use super::run;
+
+  #[test]
+  fn keep_order() {
+    let mut finished = 0;
+    let results = run((0..20).collect(), 4, |i| i * 2, |_| finished += 1);
+    assert_eq!(results, (0..20).map(|i| Ok(i * 2)).collect::<Vec<_>>());
+    assert_eq!(finished, 20);
+  }
+
+  #[test]
+  fn bounded_concurrency() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let max = Arc::new(AtomicUsize::new(0));
+
+    let (r, m) = (running.clone(), max.clone());
+    run((0..12).collect(),
+        3,
+        move |_: usize| {
+          let n = r.fetch_add(1, Ordering::SeqCst) + 1;
+          m.fetch_max(n, Ordering::SeqCst);
+          thread::sleep(Duration::from_millis(10));
+          r.fetch_sub(1, Ordering::SeqCst);
+        },
+        |_| ());
+
+    assert!(max.load(Ordering::SeqCst) <= 3);
+    assert!(max.load(Ordering::SeqCst) > 1);
+  }
+
+  #[test]
+  fn panicked_jobs() {
+    let mut finished = 0;
+    let results = run((0..6).collect(),
+                      2,
+                      |i| if i == 3 { panic!("job {}", i) } else { i },
+                      |_| finished += 1);
+    assert_eq!(results, vec![Ok(0), Ok(1), Ok(2), Err("job 3".to_owned()), Ok(4), Ok(5)]);
+    assert_eq!(finished, 5);
+  }
+}
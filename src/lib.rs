@@ -1,14 +1,30 @@
 extern crate ansi_term;
+#[cfg(test)]
+extern crate filetime;
+#[cfg(feature = "native-git")]
+extern crate flate2;
 extern crate regex;
 extern crate rustc_serialize;
+#[cfg(feature = "native-git")]
+extern crate sha1_smol;
 extern crate shellexpand;
 extern crate toml;
 extern crate url;
 extern crate walkdir;
 
+pub mod batch;
 pub mod config;
 pub mod error;
+pub mod format;
+pub mod host;
+pub mod jobs;
+pub mod manifest;
+pub mod prompt;
+pub mod query;
 pub mod repository;
 pub mod vcs;
 pub mod workspace;
 pub mod util;
+
+#[cfg(test)]
+mod testing;
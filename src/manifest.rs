@@ -0,0 +1,216 @@
+use std::path::Path;
+use rustc_serialize::json;
+use toml;
+use error::GhqError;
+use repository::{Outcome, Repository};
+use util::Output;
+use vcs::{self, CloneOptions, VCS};
+use workspace::Workspace;
+
+
+// A snapshot of all repositories in the workspace, written by `ghqrs export`.
+//
+// ```toml
+// [[repos]]
+// root = "work"
+// host = "github.com"
+// path = "hoge/fuga"
+// vcs = "git"
+// url = "https://github.com/hoge/fuga.git"
+// branch = "master"
+// revision = "0123456789abcdef0123456789abcdef01234567"
+// ```
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct Manifest {
+  pub repos: Vec<Entry>,
+}
+
+#[derive(Debug, RustcEncodable, RustcDecodable)]
+pub struct Entry {
+  // the name of the root, if it is named
+  pub root: Option<String>,
+  pub host: String,
+  pub path: String,
+  pub vcs: VCS,
+  pub url: Option<String>,
+  pub branch: Option<String>,
+  pub revision: Option<String>,
+}
+
+impl Manifest {
+  pub fn collect(workspace: &Workspace) -> Manifest {
+    let names = workspace.named_roots();
+    let repos = workspace.map_repo(|repo, root| {
+      let path = repo.local_path(root);
+      let branch = vcs::status_of(&path)
+        .ok()
+        .and_then(|s| s)
+        .map(|s| s.branch().to_owned())
+        // detached HEAD, e.g. `(v1.0.0)`
+        .and_then(|b| if b == "" || b.starts_with('(') { None } else { Some(b) });
+
+      Entry {
+        root: names.iter()
+          .find(|&&(_, r)| r == root)
+          .and_then(|&(name, _)| name.map(ToOwned::to_owned)),
+        host: repo.host().to_owned(),
+        path: repo.path().to_owned(),
+        vcs: repo.vcs(),
+        url: repo.remote_url(root).map(|url| url.as_str().to_owned()),
+        branch: branch,
+        revision: repo.vcs().revision(&path).ok().and_then(|r| r),
+      }
+    });
+
+    Manifest { repos: repos }
+  }
+
+  // parse a manifest written in either TOML or JSON.
+  pub fn parse(content: &str) -> Result<Manifest, GhqError> {
+    if content.trim_left().starts_with('{') {
+      json::decode(content).map_err(|_| "failed to parse the manifest as JSON".into())
+    } else {
+      toml::decode_str(content).ok_or("failed to parse the manifest as TOML".into())
+    }
+  }
+
+  pub fn to_toml(&self) -> String {
+    toml::encode_str(self)
+  }
+
+  pub fn to_json(&self) -> String {
+    json::as_pretty_json(self).to_string()
+  }
+}
+
+impl Entry {
+  // the repository placed at the recorded location.
+  pub fn repository(&self, workspace: &Workspace) -> Result<Repository, GhqError> {
+    let url = self.url.as_ref().ok_or("the remote URL is not recorded")?;
+    let repo = workspace.resolve_remote(url, Some(self.vcs))?;
+    Ok(repo.with_location(&self.host, &self.path))
+  }
+
+  pub fn clone_options(&self) -> CloneOptions {
+    CloneOptions {
+      branch: match self.vcs {
+        VCS::Git | VCS::Hg => self.branch.clone(),
+        _ => None,
+      },
+      ..CloneOptions::default()
+    }
+  }
+}
+
+// clone a repository of the manifest, and check out the recorded revision if given.
+// existing repositories are left as they are.
+pub fn restore(repo: &Repository,
+               root: &Path,
+               opts: &CloneOptions,
+               revision: Option<&str>,
+               out: &mut Output)
+               -> Result<Outcome, GhqError> {
+  let outcome = repo.clone_into(root, opts, false, out)?;
+  if let (Outcome::Cloned, Some(revision)) = (outcome, revision) {
+    out.println(&format!("check out '{}'", revision));
+    repo.vcs().update_to_revision(&repo.local_path(root), revision, out)?;
+  }
+  Ok(outcome)
+}
+
+
+#[cfg(test)]
+mod test_manifest {
+  use std::fs;
+  use std::path::Path;
+  use url::Url;
+  use super::{Manifest, restore};
+  use config::{Config, Root, Roots};
+  use repository::Outcome;
+  use testing::{self, git, TempDir};
+  use util::Output;
+  use vcs::{CloneOptions, VCS};
+  use workspace::Workspace;
+
+  fn workspace(root: &Path) -> Workspace {
+    Workspace::new(Config {
+      roots: Roots(vec![Root {
+                          name: Some("main".to_owned()),
+                          path: root.to_string_lossy().into_owned(),
+                        }]),
+      ..Config::default()
+    })
+  }
+
+  #[test]
+  fn parse() {
+    let manifest = Manifest::parse(r#"
+      [[repos]]
+      host = "github.com"
+      path = "hoge/fuga"
+      vcs = "git"
+      url = "https://github.com/hoge/fuga.git"
+
+      [[repos]]
+      root = "work"
+      host = "hg.example.com"
+      path = "hoge/piyo"
+      vcs = "hg"
+      revision = "0123456789ab"
+    "#)
+      .unwrap();
+    assert_eq!(manifest.repos.len(), 2);
+    assert_eq!(manifest.repos[1].vcs, VCS::Hg);
+    assert_eq!(manifest.repos[1].root, Some("work".to_owned()));
+
+    let manifest = Manifest::parse(&manifest.to_json()).unwrap();
+    assert_eq!(manifest.repos[0].url, Some("https://github.com/hoge/fuga.git".to_owned()));
+    assert!(Manifest::parse(&manifest.to_toml()).is_ok());
+  }
+
+  #[test]
+  fn export_and_restore() {
+    if !testing::available("git") {
+      return;
+    }
+    let dir = TempDir::new("manifest");
+    let upstream = dir.join("upstream/fuga");
+    fs::create_dir_all(&upstream).unwrap();
+    assert!(git(&upstream, &["init", "--quiet"]));
+    assert!(git(&upstream, &["commit", "--quiet", "--allow-empty", "-m", "first"]));
+    assert!(git(&upstream, &["commit", "--quiet", "--allow-empty", "-m", "second"]));
+
+    // clone the upstream, and go back to the first commit.
+    let mut out = Output::Capture(String::new());
+    let url = Url::from_file_path(&upstream).unwrap();
+    let old = dir.join("old");
+    workspace(&old)
+      .clone_from(url.as_str(), &old, None, &CloneOptions::default(), false, &mut out)
+      .unwrap();
+    let path = upstream.to_str().unwrap().trim_left_matches("/").to_owned();
+    let checkout = old.join("localhost").join(&path);
+    assert!(git(&checkout, &["checkout", "--quiet", "HEAD~"]));
+    let first = VCS::Git.revision(&checkout).unwrap();
+
+    let manifest = Manifest::parse(&Manifest::collect(&workspace(&old)).to_toml()).unwrap();
+    assert_eq!(manifest.repos.len(), 1);
+    assert_eq!(manifest.repos[0].root, Some("main".to_owned()));
+    assert_eq!(manifest.repos[0].path, path);
+    assert_eq!(manifest.repos[0].revision, first);
+
+    // recreate the workspace.
+    let new = dir.join("new");
+    let ws = workspace(&new);
+    for entry in &manifest.repos {
+      let repo = entry.repository(&ws).unwrap();
+      let outcome = restore(&repo,
+                            &new,
+                            &entry.clone_options(),
+                            entry.revision.as_ref().map(|s| s.as_str()),
+                            &mut out)
+        .unwrap();
+      assert_eq!(outcome, Outcome::Cloned);
+    }
+    assert_eq!(VCS::Git.revision(&new.join("localhost").join(&path)).unwrap(), first);
+  }
+}
@@ -0,0 +1,756 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use ansi_term::{Colour, Style};
+use error::GhqError;
+use vcs::Status;
+
+
+const DEFAULT_WRAPPER: &'static str = "[{vcs}]({prompt}{?partial: ?})";
+
+const DEFAULT_GIT: &'static str = "{#branch:{branch}}{?upstream: {#upstream:{upstream_symbol}}}\
+  {?index: {#index:|I +{index.added} ~{index.changed} -{index.deleted} !{index.unmerged}}}\
+  {?working: {#working:|W +{working.added} ~{working.changed} -{working.deleted} \
+  !{working.unmerged}}}{?untracked: {#untracked:|? {untracked}}}\
+  {?stash_count: {#stash:|S {stash_count}}}{?submodules: {#submodules:|SM {submodules}}}\
+  {?linked_worktree: {#worktree:|WT}}{?sparse: {#sparse:|SP}}";
+
+const DEFAULT_HG: &'static str = "{#branch:{branch}}\
+  {?diff:{#working:|+{diff.added} ~{diff.modified} x{diff.deleted} ?{diff.untracked} \
+  m{diff.missing} c{diff.renamed}}}{?refs:|{refs}}{?rev: <{rev}>}";
+
+const DEFAULT_SVN: &'static str = "{#branch:{branch}}@{revision}\
+  {?diff:{#working:|+{diff.added} ~{diff.changed} -{diff.deleted} ?{diff.untracked} \
+  !{diff.missing} C{diff.conflicts}}}{?incoming:{#behind:|In{incoming}@{incoming_revision}}}\
+  {?external:|Ex{external}}";
+
+#[cfg_attr(rustfmt, rustfmt_skip)]
+const DEFAULT_THEME: &'static [(&'static str, &'static str)] = &[
+    ("branch", "cyan")
+  , ("aligned", "cyan")
+  , ("ahead", "green")
+  , ("behind", "red")
+  , ("diverged", "yellow")
+  , ("index", "green")
+  , ("working", "red")
+  , ("untracked", "red")
+  , ("stash", "blue")
+  , ("operation", "purple bold")
+  , ("submodules", "yellow")
+  , ("worktree", "purple")
+  , ("sparse", "dimmed")
+];
+
+
+// Templates of the prompt printed by `vcs_info`.
+//
+// ```toml
+// [prompt]
+// symbols = "ascii"
+// timeout = 200
+// cache = true
+// wrapper = "({vcs}:{prompt})"
+// git = "{branch}{upstream_symbol}{?index: |I +{index.added}}{?!upstream: (local)}"
+//
+// [prompt.symbol_sets.arrows]
+// aligned = ""
+// ahead = ">"
+// behind = "<"
+// diverged = "<>"
+//
+// [prompt.themes.mine]
+// branch = "bold 208"
+// index = "green on_black"
+// ```
+//
+// `{name}` is replaced with the field of the status, and `{?name:...}` is rendered only if
+// the field is neither empty, `0` nor `false` (`{?!name:...}` is the opposite).
+// `{#style:...}` is painted with the style of the theme, and a backslash escapes the following
+// character, e.g. `\{`.
+#[derive(Default, RustcDecodable)]
+pub struct PromptConfig {
+  // the name of the symbol set, `unicode` (default) or `ascii`, or one in `symbol_sets`
+  pub symbols: Option<String>,
+  // the name of the theme, `none` (default) or `default`, or one in `themes`
+  pub theme: Option<String>,
+  // the template which wraps the prompt of each VCS
+  pub wrapper: Option<String>,
+  pub git: Option<String>,
+  pub hg: Option<String>,
+  pub svn: Option<String>,
+  pub symbol_sets: Option<BTreeMap<String, SymbolSetConfig>>,
+  // styles of each segment, e.g. `branch = "cyan bold"`
+  pub themes: Option<BTreeMap<String, BTreeMap<String, String>>>,
+  // milliseconds to wait for the status before showing the partial one
+  pub timeout: Option<u64>,
+  // reuse the status while the repository is unchanged, see `vcs::cache`
+  pub cache: Option<bool>,
+  // seconds until cached statuses expire, `vcs::cache::DEFAULT_TTL` by default
+  pub cache_ttl: Option<u64>,
+}
+
+// the fields left out are taken from the `unicode` set.
+#[derive(RustcDecodable)]
+pub struct SymbolSetConfig {
+  pub aligned: Option<String>,
+  pub ahead: Option<String>,
+  pub behind: Option<String>,
+  pub diverged: Option<String>,
+}
+
+
+// Symbols of the relationship with the upstream branch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbols {
+  pub aligned: String,
+  pub ahead: String,
+  pub behind: String,
+  pub diverged: String,
+}
+
+impl Symbols {
+  pub fn unicode() -> Symbols {
+    Symbols {
+      aligned: "≡".to_owned(),
+      ahead: "↑".to_owned(),
+      behind: "↓".to_owned(),
+      diverged: "↕".to_owned(),
+    }
+  }
+
+  pub fn ascii() -> Symbols {
+    Symbols {
+      aligned: "=".to_owned(),
+      ahead: "A".to_owned(),
+      behind: "B".to_owned(),
+      diverged: "AB".to_owned(),
+    }
+  }
+
+  fn from_config(config: &SymbolSetConfig) -> Symbols {
+    let base = Symbols::unicode();
+    Symbols {
+      aligned: config.aligned.clone().unwrap_or(base.aligned),
+      ahead: config.ahead.clone().unwrap_or(base.ahead),
+      behind: config.behind.clone().unwrap_or(base.behind),
+      diverged: config.diverged.clone().unwrap_or(base.diverged),
+    }
+  }
+
+  // the symbol for the numbers of commits ahead of and behind the upstream.
+  pub fn upstream(&self, ahead: usize, behind: usize) -> &str {
+    match (ahead, behind) {
+      (0, 0) => &self.aligned,
+      (_, 0) => &self.ahead,
+      (0, _) => &self.behind,
+      _ => &self.diverged,
+    }
+  }
+}
+
+
+// Values referred by templates.
+pub trait Fields {
+  fn field(&self, name: &str, symbols: &Symbols) -> Option<String>;
+
+  // the name of style which is used in place of `name` for the current status,
+  // e.g. `upstream` is painted as `ahead` or `behind`.
+  fn style(&self, _name: &str) -> Option<&'static str> {
+    None
+  }
+}
+
+
+// Styles of segments in the prompt.
+#[derive(Default)]
+pub struct Theme {
+  styles: BTreeMap<String, Style>,
+}
+
+impl Theme {
+  pub fn from_map<'a, I>(styles: I) -> Result<Theme, GhqError>
+    where I: IntoIterator<Item = (&'a str, &'a str)>
+  {
+    let mut theme = Theme::default();
+    for (name, spec) in styles {
+      theme.styles.insert(name.to_owned(), parse_style(spec)?);
+    }
+    Ok(theme)
+  }
+
+  pub fn get(&self, name: &str) -> Style {
+    self.styles.get(name).cloned().unwrap_or_default()
+  }
+
+  fn contains(&self, name: &str) -> bool {
+    self.styles.contains_key(name)
+  }
+}
+
+// parse the style written as words, e.g. `bold red on_black`.
+// colours are either names, numbers of 256 colours or `#rrggbb`.
+pub fn parse_style(spec: &str) -> Result<Style, GhqError> {
+  let mut style = Style::new();
+  for word in spec.split_whitespace() {
+    style = match word {
+      "bold" => style.bold(),
+      "dimmed" => style.dimmed(),
+      "italic" => style.italic(),
+      "underline" => style.underline(),
+      "blink" => style.blink(),
+      "reverse" => style.reverse(),
+      "hidden" => style.hidden(),
+      "strikethrough" => style.strikethrough(),
+      _ if word.starts_with("on_") => {
+        style.on(parse_colour(&word[3..]).ok_or(GhqError::InvalidStyle(spec.to_owned()))?)
+      }
+      _ => style.fg(parse_colour(word).ok_or(GhqError::InvalidStyle(spec.to_owned()))?),
+    };
+  }
+  Ok(style)
+}
+
+fn parse_colour(s: &str) -> Option<Colour> {
+  match s {
+    "black" => Some(Colour::Black),
+    "red" => Some(Colour::Red),
+    "green" => Some(Colour::Green),
+    "yellow" => Some(Colour::Yellow),
+    "blue" => Some(Colour::Blue),
+    "purple" | "magenta" => Some(Colour::Purple),
+    "cyan" => Some(Colour::Cyan),
+    "white" => Some(Colour::White),
+    _ if s.starts_with('#') && s.len() == 7 => {
+      let rgb = u32::from_str_radix(&s[1..], 16).ok()?;
+      Some(Colour::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+    }
+    _ => s.parse().ok().map(Colour::Fixed),
+  }
+}
+
+
+// The shell which the prompt is written for.
+//
+// the escape sequences of colours are wrapped so that the shell doesn't count them as the
+// width of the prompt. fish needs no wrapper since it recognizes them by itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shell {
+  Plain,
+  // `\[ \]`, which is interpreted when the output is put into `PS1`
+  // (e.g. `PROMPT_COMMAND='PS1="$(vcs_info --shell bash) \$ "'`)
+  Bash,
+  // `%{ %}`, which is interpreted when the output is substituted in `PROMPT` with
+  // `prompt_subst` (e.g. `setopt prompt_subst; PROMPT='$(vcs_info --shell zsh) %# '`)
+  Zsh,
+  Fish,
+}
+
+impl Default for Shell {
+  fn default() -> Shell {
+    Shell::Plain
+  }
+}
+
+impl FromStr for Shell {
+  type Err = GhqError;
+
+  fn from_str(s: &str) -> Result<Shell, GhqError> {
+    match s {
+      "plain" => Ok(Shell::Plain),
+      "bash" => Ok(Shell::Bash),
+      "zsh" => Ok(Shell::Zsh),
+      "fish" => Ok(Shell::Fish),
+      _ => Err(GhqError::UnknownShell(s.to_owned())),
+    }
+  }
+}
+
+impl Shell {
+  // wrap the escape sequence as zero-width characters.
+  fn zero_width(&self, seq: &str) -> String {
+    if seq == "" {
+      return String::new();
+    }
+    match *self {
+      Shell::Bash => format!("\\[{}\\]", seq),
+      Shell::Zsh => format!("%{{{}%}}", seq),
+      Shell::Plain | Shell::Fish => seq.to_owned(),
+    }
+  }
+
+  // escape the characters of field values which the shell would interpret in prompts, since
+  // branch names such as `$(touch${IFS}x)` are valid.
+  fn escape(&self, value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+      match (*self, c) {
+        // backslashes are decoded once as the escapes of `PS1`, and once more by the expansion
+        // of `promptvars`.
+        (Shell::Bash, '\\') => escaped.push_str("\\\\\\\\"),
+        (Shell::Bash, '$') | (Shell::Bash, '`') => {
+          escaped.push_str("\\\\");
+          escaped.push(c);
+        }
+        // the output of a command substitution is not expanded again, but `prompt_percent`
+        // still interprets `%` in it.
+        (Shell::Zsh, '%') => escaped.push_str("%%"),
+        _ => escaped.push(c),
+      }
+    }
+    escaped
+  }
+}
+
+
+// Options of rendering given by the command line, which override the configuration.
+#[derive(Debug, Clone, Default)]
+pub struct PromptOptions {
+  pub symbols: Option<String>,
+  pub theme: Option<String>,
+  pub shell: Shell,
+}
+
+pub struct Prompt {
+  wrapper: String,
+  git: String,
+  hg: String,
+  svn: String,
+  symbols: Symbols,
+  theme: Theme,
+  shell: Shell,
+}
+
+impl Default for Prompt {
+  fn default() -> Prompt {
+    Prompt {
+      wrapper: DEFAULT_WRAPPER.to_owned(),
+      git: DEFAULT_GIT.to_owned(),
+      hg: DEFAULT_HG.to_owned(),
+      svn: DEFAULT_SVN.to_owned(),
+      symbols: Symbols::unicode(),
+      theme: Theme::default(),
+      shell: Shell::Plain,
+    }
+  }
+}
+
+impl Prompt {
+  pub fn new(config: Option<&PromptConfig>, opts: &PromptOptions) -> Result<Prompt, GhqError> {
+    let default = PromptConfig::default();
+    let config = config.unwrap_or(&default);
+
+    let mut prompt = Prompt::default();
+    if let Some(ref wrapper) = config.wrapper {
+      prompt.wrapper = wrapper.clone();
+    }
+    if let Some(ref git) = config.git {
+      prompt.git = git.clone();
+    }
+    if let Some(ref hg) = config.hg {
+      prompt.hg = hg.clone();
+    }
+    if let Some(ref svn) = config.svn {
+      prompt.svn = svn.clone();
+    }
+
+    let name = opts.symbols.as_ref().or(config.symbols.as_ref()).map_or("unicode", |s| s.as_str());
+    prompt.symbols = match config.symbol_sets.as_ref().and_then(|sets| sets.get(name)) {
+      Some(set) => Symbols::from_config(set),
+      None => builtin_symbols(name)?,
+    };
+
+    let name = opts.theme.as_ref().or(config.theme.as_ref()).map_or("none", |s| s.as_str());
+    prompt.theme = match config.themes.as_ref().and_then(|themes| themes.get(name)) {
+      Some(styles) => Theme::from_map(styles.iter().map(|(k, v)| (k.as_str(), v.as_str())))?,
+      None => builtin_theme(name)?,
+    };
+
+    prompt.shell = opts.shell;
+    Ok(prompt)
+  }
+
+  pub fn symbols(&self) -> &Symbols {
+    &self.symbols
+  }
+
+  // the prompt of the status, without the wrapper.
+  pub fn body(&self, status: &Status) -> String {
+    match *status {
+      Status::Git(ref s) => self.render_template(&self.git, s),
+      Status::Hg(ref s) => self.render_template(&self.hg, s),
+      Status::Svn(ref s) => self.render_template(&self.svn, s),
+    }
+  }
+
+  pub fn render(&self, status: &Status) -> String {
+    self.wrap(status, false)
+  }
+
+  // render the status which was given up collecting, e.g. which has the branch only.
+  pub fn render_partial(&self, status: &Status) -> String {
+    self.wrap(status, true)
+  }
+
+  fn wrap(&self, status: &Status, partial: bool) -> String {
+    let vcs = match *status {
+      Status::Git(_) => "git",
+      Status::Hg(_) => "hg",
+      Status::Svn(_) => "svn",
+    };
+    // the body is already escaped for the shell.
+    let wrapper = Wrapper {
+      vcs: vcs,
+      prompt: self.body(status),
+      partial: partial,
+    };
+    Renderer {
+      prompt: self,
+      fields: &wrapper,
+      chars: self.wrapper.chars().collect(),
+      escape: false,
+    }
+    .render()
+  }
+
+  // render the template with the fields.
+  // unknown placeholders are left as they are.
+  pub fn render_template<F: Fields>(&self, template: &str, fields: &F) -> String {
+    Renderer {
+      prompt: self,
+      fields: fields,
+      chars: template.chars().collect(),
+      escape: true,
+    }
+    .render()
+  }
+}
+
+fn builtin_symbols(name: &str) -> Result<Symbols, GhqError> {
+  match name {
+    "unicode" => Ok(Symbols::unicode()),
+    "ascii" => Ok(Symbols::ascii()),
+    _ => Err(GhqError::UnknownSymbolSet(name.to_owned())),
+  }
+}
+
+fn builtin_theme(name: &str) -> Result<Theme, GhqError> {
+  match name {
+    "none" => Ok(Theme::default()),
+    "default" => Theme::from_map(DEFAULT_THEME.iter().cloned()),
+    _ => Err(GhqError::UnknownTheme(name.to_owned())),
+  }
+}
+
+struct Wrapper<'a> {
+  vcs: &'a str,
+  prompt: String,
+  partial: bool,
+}
+
+impl<'a> Fields for Wrapper<'a> {
+  fn field(&self, name: &str, _: &Symbols) -> Option<String> {
+    match name {
+      "vcs" => Some(self.vcs.to_owned()),
+      "prompt" => Some(self.prompt.clone()),
+      "partial" => Some(self.partial.to_string()),
+      _ => None,
+    }
+  }
+}
+
+
+struct Renderer<'a, F: Fields + 'a> {
+  prompt: &'a Prompt,
+  fields: &'a F,
+  chars: Vec<char>,
+  // whether field values are escaped for the shell
+  escape: bool,
+}
+
+impl<'a, F: Fields> Renderer<'a, F> {
+  fn render(&self) -> String {
+    let mut pos = 0;
+    let mut ret = String::new();
+    // a stray `}` at the top level is written literally.
+    while pos < self.chars.len() {
+      ret += &self.expand(&mut pos, Style::new());
+      if pos < self.chars.len() {
+        ret.push('}');
+        pos += 1;
+      }
+    }
+    ret
+  }
+
+  // expand the template until the end of the section (an unmatched `}`, left at `pos`).
+  // `style` is the style of the enclosing section, which is restored after nested ones.
+  fn expand(&self, pos: &mut usize, style: Style) -> String {
+    let chars = &self.chars;
+    let mut ret = String::new();
+    while *pos < chars.len() {
+      match chars[*pos] {
+        '}' => return ret,
+        '\\' if *pos + 1 < chars.len() => {
+          ret.push(chars[*pos + 1]);
+          *pos += 2;
+        }
+        '{' if chars.get(*pos + 1) == Some(&'?') || chars.get(*pos + 1) == Some(&'#') => {
+          let start = *pos;
+          let colon = match chars[start..].iter().position(|&c| c == ':') {
+            Some(colon) => start + colon,
+            None => {
+              ret.extend(&chars[start..]);
+              *pos = chars.len();
+              return ret;
+            }
+          };
+          let name: String = chars[start + 2..colon].iter().collect();
+          *pos = colon + 1;
+
+          if chars[start + 1] == '#' {
+            let inner = self.style(&name);
+            let body = self.expand(pos, inner);
+            ret += &self.paint(style, inner, &body);
+          } else {
+            let body = self.expand(pos, style);
+            let (negated, name) = if name.starts_with('!') {
+              (true, &name[1..])
+            } else {
+              (false, name.as_str())
+            };
+            if truthy(self.fields.field(name, &self.prompt.symbols)) != negated {
+              ret += &body;
+            }
+          }
+          // skip the closing brace
+          *pos += 1;
+        }
+        '{' => {
+          let start = *pos;
+          match chars[start..].iter().position(|&c| c == '}') {
+            Some(end) => {
+              let name: String = chars[start + 1..start + end].iter().collect();
+              match self.fields.field(&name, &self.prompt.symbols) {
+                Some(ref value) if self.escape => ret += &self.prompt.shell.escape(value),
+                Some(value) => ret += &value,
+                None => ret.extend(&chars[start..start + end + 1]),
+              }
+              *pos = start + end + 1;
+            }
+            None => {
+              ret.extend(&chars[start..]);
+              *pos = chars.len();
+            }
+          }
+        }
+        c => {
+          ret.push(c);
+          *pos += 1;
+        }
+      }
+    }
+    ret
+  }
+
+  fn style(&self, name: &str) -> Style {
+    let theme = &self.prompt.theme;
+    theme.get(self.fields.style(name).filter(|s| theme.contains(s)).unwrap_or(name))
+  }
+
+  // paint the body of a section with `inner`, and go back to `outer` after it.
+  fn paint(&self, outer: Style, inner: Style, body: &str) -> String {
+    if inner == outer || body == "" {
+      return body.to_owned();
+    }
+    let shell = self.prompt.shell;
+    format!("{}{}{}",
+            shell.zero_width(&outer.infix(inner).to_string()),
+            body,
+            shell.zero_width(&inner.infix(outer).to_string()))
+  }
+}
+
+fn truthy(value: Option<String>) -> bool {
+  match value {
+    Some(ref v) => v != "" && v != "0" && v != "false",
+    None => false,
+  }
+}
+
+
+#[cfg(test)]
+mod test_prompt {
+  use std::collections::BTreeMap;
+  use toml;
+  use super::{Fields, Prompt, PromptConfig, PromptOptions, Shell, Symbols};
+  use vcs::{git, hg, svn, Status};
+
+  struct Map(BTreeMap<&'static str, &'static str>);
+
+  impl Fields for Map {
+    fn field(&self, name: &str, _: &Symbols) -> Option<String> {
+      self.0.get(name).map(|&v| v.to_owned())
+    }
+  }
+
+  fn git_status() -> git::Status {
+    git::Status {
+      branch: "master".to_owned(),
+      upstream: "origin/master".to_owned(),
+      ahead_by: 2,
+      index: Some(git::DiffInfo { added: 1, modified: 1, renamed: 1, ..Default::default() }),
+      untracked: 3,
+      ..Default::default()
+    }
+  }
+
+  #[test]
+  fn sections() {
+    let map = Map(vec![("a", "1"), ("zero", "0"), ("empty", ""), ("no", "false")]
+      .into_iter()
+      .collect());
+    let prompt = Prompt::default();
+    assert_eq!(prompt.render_template("<{a}{b}>", &map), "<1{b}>");
+    assert_eq!(prompt.render_template("{?a:[{a}]}{?zero:zero}{?empty:empty}{?no:no}{?b:b}", &map),
+               "[1]");
+    assert_eq!(prompt.render_template("{?!zero:{?a:{?!b:nested}}}", &map), "nested");
+    assert_eq!(prompt.render_template(r"\{a\} {a}} {?a", &map), "{a} 1} {?a");
+    // no styles without themes
+    assert_eq!(prompt.render_template("{#branch:{a}}", &map), "1");
+  }
+
+  #[test]
+  fn defaults() {
+    let prompt = Prompt::default();
+    assert_eq!(prompt.render(&Status::Git(git_status())),
+               "[git](master ↑ |I +1 ~2 -0 !0 |? 3)");
+
+    let hg = hg::Status {
+      branch: "default".to_owned(),
+      tags: vec!["tip".to_owned(), "v1.0".to_owned()],
+      active: "feature".to_owned(),
+      rev: "2:0123456789ab".to_owned(),
+      diff: Some(hg::Diff { modified: 2, ..Default::default() }),
+      ..Default::default()
+    };
+    assert_eq!(prompt.render(&Status::Hg(hg)),
+               "[hg](default|+0 ~2 x0 ?0 m0 c0|feature tip, v1.0 <2:0123456789ab>)");
+
+    let svn = svn::Status {
+      branch: "trunk".to_owned(),
+      revision: "42".to_owned(),
+      incoming: 1,
+      incoming_revision: 43,
+      ..Default::default()
+    };
+    assert_eq!(prompt.render(&Status::Svn(svn)), "[svn](trunk@42|In1@43)");
+
+    let git = git::Status {
+      branch: "master".to_owned(),
+      submodules: 2,
+      linked_worktree: true,
+      sparse: true,
+      ..Default::default()
+    };
+    assert_eq!(prompt.render(&Status::Git(git)), "[git](master |SM 2 |WT |SP)");
+
+    let partial = git::Status { branch: "master".to_owned(), ..Default::default() };
+    assert_eq!(prompt.render_partial(&Status::Git(partial)), "[git](master ?)");
+  }
+
+  #[test]
+  fn configured() {
+    let config: PromptConfig = toml::decode_str(r#"
+      symbols = "arrows"
+      wrapper = "{vcs}:{prompt}"
+      git = "{branch}{upstream_symbol}{?index: I{index.added}}{?!stash_count: (no stash)}"
+
+      [symbol_sets.arrows]
+      ahead = ">"
+    "#)
+      .unwrap();
+
+    let prompt = Prompt::new(Some(&config), &PromptOptions::default()).unwrap();
+    assert_eq!(prompt.render(&Status::Git(git_status())), "git:master> I1 (no stash)");
+
+    // a built-in set given by the command line
+    let prompt = Prompt::new(Some(&config), &symbols("ascii")).unwrap();
+    assert_eq!(prompt.body(&Status::Git(git_status())), "masterA I1 (no stash)");
+
+    assert!(Prompt::new(Some(&config), &symbols("unknown")).is_err());
+    assert!(Prompt::new(None, &symbols("arrows")).is_err());
+  }
+
+  fn symbols(name: &str) -> PromptOptions {
+    PromptOptions { symbols: Some(name.to_owned()), ..PromptOptions::default() }
+  }
+
+  fn themed(shell: Shell) -> Prompt {
+    let config: PromptConfig = toml::decode_str(r##"
+      git = "{#branch:{branch}} {#upstream:{upstream_symbol}{#stash:!}}"
+
+      [themes.mine]
+      branch = "bold red"
+      ahead = "green"
+      stash = "#0080ff on_8"
+    "##)
+      .unwrap();
+    let opts = PromptOptions {
+      theme: Some("mine".to_owned()),
+      shell: shell,
+      ..PromptOptions::default()
+    };
+    Prompt::new(Some(&config), &opts).unwrap()
+  }
+
+  #[test]
+  fn themes() {
+    let status = Status::Git(git_status());
+    assert_eq!(themed(Shell::Plain).body(&status),
+               "\x1b[1;31mmaster\x1b[0m \x1b[32m↑\
+                \x1b[48;5;8;38;2;0;128;255m!\x1b[0m\x1b[32m\x1b[0m");
+    assert_eq!(themed(Shell::Fish).body(&status), themed(Shell::Plain).body(&status));
+    assert_eq!(themed(Shell::Bash).body(&status),
+               "\\[\x1b[1;31m\\]master\\[\x1b[0m\\] \\[\x1b[32m\\]↑\
+                \\[\x1b[48;5;8;38;2;0;128;255m\\]!\\[\x1b[0m\x1b[32m\\]\\[\x1b[0m\\]");
+    assert_eq!(themed(Shell::Zsh).body(&status),
+               "%{\x1b[1;31m%}master%{\x1b[0m%} %{\x1b[32m%}↑\
+                %{\x1b[48;5;8;38;2;0;128;255m%}!%{\x1b[0m\x1b[32m%}%{\x1b[0m%}");
+
+    // the default theme paints the segments of the default template.
+    let prompt = Prompt::new(None, &PromptOptions {
+        theme: Some("default".to_owned()),
+        ..PromptOptions::default()
+      })
+      .unwrap();
+    assert_eq!(prompt.render(&Status::Git(git_status())),
+               "[git](\x1b[36mmaster\x1b[0m \x1b[32m↑\x1b[0m \x1b[32m|I +1 ~2 -0 !0\x1b[0m \
+                \x1b[31m|? 3\x1b[0m)");
+  }
+
+  #[test]
+  fn shells() {
+    let status = Status::Git(git::Status { branch: "100%\\".to_owned(), ..Default::default() });
+    assert_eq!(Prompt::default().body(&status), "100%\\");
+    let opts = |shell| PromptOptions { shell: shell, ..PromptOptions::default() };
+    assert_eq!(Prompt::new(None, &opts(Shell::Zsh)).unwrap().body(&status), "100%%\\");
+    assert_eq!(Prompt::new(None, &opts(Shell::Bash)).unwrap().body(&status), "100%\\\\\\\\");
+
+    // branch names which would run commands if they were expanded
+    let status = Status::Git(git::Status {
+      branch: "$(touch${IFS}pwned)`id`!".to_owned(),
+      ..Default::default()
+    });
+    assert_eq!(Prompt::new(None, &opts(Shell::Bash)).unwrap().body(&status),
+               r"\\$(touch\\${IFS}pwned)\\`id\\`!");
+    assert_eq!(Prompt::new(None, &opts(Shell::Zsh)).unwrap().body(&status),
+               "$(touch${IFS}pwned)`id`!");
+    assert_eq!(Prompt::new(None, &opts(Shell::Fish)).unwrap().body(&status),
+               "$(touch${IFS}pwned)`id`!");
+    assert!("tcsh".parse::<Shell>().is_err());
+    assert!(Prompt::new(None, &PromptOptions {
+        theme: Some("mine".to_owned()),
+        ..PromptOptions::default()
+      })
+      .is_err());
+  }
+}
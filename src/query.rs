@@ -0,0 +1,134 @@
+use regex::Regex;
+use repository::Repository;
+use vcs::VCS;
+
+
+enum Pattern {
+  Regex(Regex),
+  Substring(String),
+}
+
+pub struct Query {
+  pattern: Option<(String, Pattern)>,
+  exact: bool,
+  vcs: Option<VCS>,
+}
+
+impl Query {
+  pub fn new(pattern: Option<&str>) -> Query {
+    let pattern = pattern.map(|s| {
+      // fall back to substring matching if the pattern is not a valid regex.
+      let pat = Regex::new(s).map(Pattern::Regex).unwrap_or(Pattern::Substring(s.to_owned()));
+      (s.to_owned(), pat)
+    });
+
+    Query {
+      pattern: pattern,
+      exact: false,
+      vcs: None,
+    }
+  }
+
+  pub fn exact(mut self, exact: bool) -> Query {
+    self.exact = exact;
+    self
+  }
+
+  pub fn vcs(mut self, vcs: Option<VCS>) -> Query {
+    self.vcs = vcs;
+    self
+  }
+
+  pub fn matches(&self, repo: &Repository) -> bool {
+    if let Some(vcs) = self.vcs {
+      if repo.vcs() != vcs {
+        return false;
+      }
+    }
+
+    match self.pattern {
+      None => true,
+      // `host/owner/repo`, `owner/repo` or `repo`
+      Some((ref s, _)) if self.exact => {
+        repo.full_name() == *s || repo.path() == s || repo.name() == s
+      }
+      Some((_, Pattern::Regex(ref re))) => re.is_match(&repo.full_name()),
+      Some((_, Pattern::Substring(ref s))) => repo.full_name().contains(s.as_str()),
+    }
+  }
+}
+
+
+// Compute the shortest suffix of each name which identifies it among all of `names`.
+pub fn unique_names(names: &[String]) -> Vec<String> {
+  let splitted: Vec<Vec<&str>> = names.iter().map(|name| name.split("/").collect()).collect();
+
+  splitted.iter()
+    .map(|components| {
+      for n in 1..components.len() {
+        let suffix = &components[components.len() - n..];
+        let conflicted = splitted.iter()
+          .filter(|other| other.len() >= n && &other[other.len() - n..] == suffix)
+          .count() > 1;
+        if !conflicted {
+          return suffix.join("/");
+        }
+      }
+      components.join("/")
+    })
+    .collect()
+}
+
+
+#[cfg(test)]
+mod test_query {
+  use super::{Query, unique_names};
+  use host::Hosts;
+  use repository::Repository;
+  use vcs::VCS;
+
+  fn repo(s: &str) -> Repository {
+    Repository::from_local(s, &Hosts::default()).unwrap()
+  }
+
+  #[test]
+  fn no_pattern() {
+    assert!(Query::new(None).matches(&repo("github.com/hoge/fuga")));
+  }
+
+  #[test]
+  fn substring_and_regex() {
+    assert!(Query::new(Some("hoge/fu")).matches(&repo("github.com/hoge/fuga")));
+    assert!(Query::new(Some("^github\\.com/.+/fuga$")).matches(&repo("github.com/hoge/fuga")));
+    assert!(!Query::new(Some("^fuga")).matches(&repo("github.com/hoge/fuga")));
+    // invalid regex
+    assert!(Query::new(Some("hoge(")).matches(&repo("github.com/hoge(/fuga")));
+  }
+
+  #[test]
+  fn exact() {
+    let r = repo("github.com/hoge/fuga");
+    assert!(Query::new(Some("github.com/hoge/fuga")).exact(true).matches(&r));
+    assert!(Query::new(Some("hoge/fuga")).exact(true).matches(&r));
+    assert!(Query::new(Some("fuga")).exact(true).matches(&r));
+    assert!(!Query::new(Some("fug")).exact(true).matches(&r));
+    assert!(!Query::new(Some("hoge")).exact(true).matches(&r));
+  }
+
+  #[test]
+  fn vcs() {
+    let r = repo("github.com/hoge/fuga").with_vcs(VCS::Hg);
+    assert!(Query::new(None).vcs(Some(VCS::Hg)).matches(&r));
+    assert!(!Query::new(None).vcs(Some(VCS::Git)).matches(&r));
+  }
+
+  #[test]
+  fn unique() {
+    let names = vec!["github.com/hoge/fuga".to_owned(),
+                     "github.com/piyo/fuga".to_owned(),
+                     "github.com/hoge/foo".to_owned(),
+                     "gist.github.com/0bacdbefa19f".to_owned()];
+    assert_eq!(unique_names(&names),
+               vec!["hoge/fuga", "piyo/fuga", "foo", "0bacdbefa19f"]);
+  }
+}
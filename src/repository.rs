@@ -1,20 +1,16 @@
+use std::fmt;
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use regex::Regex;
 use url::Url;
 use error::GhqError;
-use vcs::VCS;
+use host::Hosts;
+use util::{Output, same_path};
+use vcs::{self, CloneOptions, VCS};
 
 
-#[allow(dead_code)]
-#[cfg_attr(rustfmt, rustfmt_skip)]
-const KNOWN_HOSTS: &'static [(&'static str, usize)] = &[
-    ("github.com", 2)
-  , ("gist.github.com", 1)
-  , ("bitbucket.org", 2)
-  , ("gitlab.com", 2)
-];
-
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Repository {
   url: Option<Url>,
   vcs: VCS,
@@ -23,7 +19,7 @@ pub struct Repository {
 }
 
 impl Repository {
-  pub fn from_local<P: AsRef<Path>>(path: P) -> Result<Repository, GhqError> {
+  pub fn from_local<P: AsRef<Path>>(path: P, hosts: &Hosts) -> Result<Repository, GhqError> {
     let path = path.as_ref().to_string_lossy().replace("\\", "/");
     let splitted: Vec<_> = path.splitn(2, '/').collect();
     if splitted.len() < 2 {
@@ -31,14 +27,11 @@ impl Repository {
     }
 
     let host = splitted[0].to_owned();
+    let rule = hosts.get(&host);
 
     // check depth
     let path_depth = splitted[1].split("/").count();
-    let host_depth = match host.as_str() {
-      "gist.github.com" => 1,
-      _ => 2, // github.com, bitbucket.org, gitlab.com, ...
-    };
-    if path_depth != host_depth {
+    if !rule.accepts(path_depth) {
       Err("wrong depth in path")?;
     }
 
@@ -46,46 +39,142 @@ impl Repository {
 
     Ok(Repository {
       url: None,
-      vcs: VCS::Git,
+      vcs: rule.vcs.unwrap_or(VCS::Git),
       host: host,
       path: path,
     })
   }
 
-  pub fn from_remote(s: &str) -> Result<Repository, GhqError> {
-    if let Ok(url) = Url::parse(s) {
-      let host = url.host_str().ok_or("cannot retrieve host information")?.to_owned();
-      let path = url.path().trim_left_matches("/").trim_right_matches(".git").to_owned();
+  pub fn from_remote(s: &str, hosts: &Hosts) -> Result<Repository, GhqError> {
+    // `<vcs>::<url>`, the same form as remote helpers of Git
+    let (vcs, s) = match s.find("::").map(|pos| (s[..pos].parse::<VCS>(), pos)) {
+      Some((Ok(vcs), pos)) => (Some(vcs), &s[pos + 2..]),
+      _ => (None, s),
+    };
+
+    let mut repo = if let Some(url) = parse_url(s) {
+      let host = match url.host_str() {
+        Some(host) if host != "" => host.to_owned(),
+        _ if url.scheme() == "file" => "localhost".to_owned(),
+        _ => Err("cannot retrieve host information")?,
+      };
+      let path = url.path().trim_left_matches("/").trim_right_matches(".git");
+      // the home directory of scp-like URLs is not a part of the location.
+      let path = match path.starts_with("~/") {
+        true => path[2..].to_owned(),
+        false => path.to_owned(),
+      };
       // TODO: check if given URL is valid
 
-      Ok(Repository {
+      Repository {
         url: Some(url),
         vcs: VCS::Git,
         host: host,
         path: path,
-      })
+      }
 
     } else {
       let path: Vec<_> = s.split("/").collect();
       let (host, path) = match path.len() {
         0 => Err("unsupported pattern to resolve remote URL")?,
         1 => ("github.com".to_owned(), vec![path[0], path[0]]),
-        2 => ("github.com".to_owned(), vec![path[0], path[1]]),
+        2 if !hosts.contains(path[0]) => ("github.com".to_owned(), vec![path[0], path[1]]),
         _ => (path[0].to_owned(), Vec::from(&path[1..])),
       };
 
-      let url = Url::parse(&format!("{}://{}/{}.git",
-                                    "https",
-                                    host,
-                                    path.iter().take(2).cloned().collect::<Vec<_>>().join("/")))?;
+      // the rest of components (e.g. `tree/master`) is not a part of the remote URL.
+      let url_depth = hosts.get(&host).url_depth(path.len());
+      let repo_path = path.iter().take(url_depth).cloned().collect::<Vec<_>>().join("/");
+      let url = hosts.remote_url(&host, &repo_path, vcs)?;
 
-      Ok(Repository {
+      Repository {
         url: Some(url),
         vcs: VCS::Git,
         host: host,
         path: path.join("/"),
+      }
+    };
+
+    // detect VCS of the remote repository, in order of:
+    // explicit prefix, URL scheme, configuration of the host and probing a local repository.
+    let vcs = vcs.or_else(|| repo.url.as_ref().and_then(VCS::from_url))
+      .or_else(|| hosts.get(&repo.host).vcs)
+      .or_else(|| {
+        repo.url
+          .as_ref()
+          .and_then(|url| url.to_file_path().ok())
+          .and_then(VCS::probe)
       })
+      .unwrap_or(VCS::Git);
+    repo.vcs = vcs;
+
+    Ok(repo)
+  }
+
+  pub fn host(&self) -> &str {
+    &self.host
+  }
+
+  pub fn path(&self) -> &str {
+    &self.path
+  }
+
+  // `host/owner/repo`
+  pub fn full_name(&self) -> String {
+    format!("{}/{}", self.host, self.path)
+  }
+
+  // the path without its last component, e.g. `owner` or `group/subgroup`
+  pub fn owner(&self) -> &str {
+    self.path.rfind("/").map(|pos| &self.path[..pos]).unwrap_or("")
+  }
+
+  // the last component of the path
+  pub fn name(&self) -> &str {
+    self.path.rsplit("/").next().unwrap_or(&self.path)
+  }
+
+  pub fn url(&self) -> Option<&Url> {
+    self.url.as_ref()
+  }
+
+  // the remote URL given at cloning, or the one recorded in the local repository.
+  pub fn remote_url<P: AsRef<Path>>(&self, root: P) -> Option<Url> {
+    if let Some(ref url) = self.url {
+      return Some(url.clone());
     }
+
+    let remote = self.vcs.remote_url(&self.local_path(root)).ok().and_then(|s| s)?;
+    parse_url(&remote)
+  }
+
+  // check whether the remote URL agrees with the location of the local repository.
+  // returns the repository resolved from the remote URL if they disagree,
+  // e.g. a fork is cloned into the directory of its upstream.
+  pub fn remote_mismatch<P: AsRef<Path>>(&self, root: P, hosts: &Hosts) -> Option<Repository> {
+    let url = self.remote_url(root)?;
+    let remote = Repository::from_remote(url.as_str(), hosts).ok()?;
+    if remote.host == self.host && remote.path == self.path {
+      None
+    } else {
+      Some(remote)
+    }
+  }
+
+  pub fn vcs(&self) -> VCS {
+    self.vcs
+  }
+
+  pub fn with_vcs(mut self, vcs: VCS) -> Repository {
+    self.vcs = vcs;
+    self
+  }
+
+  // place the repository at `host/path` regardless of its remote URL.
+  pub fn with_location(mut self, host: &str, path: &str) -> Repository {
+    self.host = host.to_owned();
+    self.path = path.to_owned();
+    self
   }
 
   pub fn local_path<P: AsRef<Path>>(&self, root: P) -> PathBuf {
@@ -95,20 +184,273 @@ impl Repository {
       .to_owned()
   }
 
-  pub fn clone_into<P: AsRef<Path>>(&self, root: P) -> Result<(), GhqError> {
+  pub fn clone_into<P: AsRef<Path>>(&self,
+                                    root: P,
+                                    opts: &CloneOptions,
+                                    update: bool,
+                                    out: &mut Output)
+                                    -> Result<Outcome, GhqError> {
     if let Some(ref url) = self.url {
       let dest = root.as_ref().join(&self.host).join(&self.path);
 
       if dest.exists() {
-        println!("The target has already existed: {}", dest.display());
-        return Ok(());
+        if update {
+          return self.update(root, out);
+        }
+        out.println(&format!("The target has already existed: {}", dest.display()));
+        return Ok(Outcome::Skipped);
       }
 
-      println!("clone '{}' into '{}'", url.as_str(), dest.display());
-      self.vcs.clone_repository(url, dest.as_path())?;
+      out.println(&format!("clone '{}' into '{}'", url.as_str(), dest.display()));
+      if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+      }
+      self.vcs.clone_repository(url, dest.as_path(), opts, out)?;
+      Ok(Outcome::Cloned)
+
+    } else {
+      Ok(Outcome::Skipped)
     }
+  }
+
+  // move (or link) an existing local repository at `src` into the workspace.
+  pub fn import_from<P: AsRef<Path>>(&self,
+                                     src: &Path,
+                                     root: P,
+                                     link: bool,
+                                     dry_run: bool,
+                                     out: &mut Output)
+                                     -> Result<Outcome, GhqError> {
+    let src = src.canonicalize()?;
+    let dest = self.local_path(root);
+    if fs::symlink_metadata(&dest).is_ok() {
+      if same_path(&src, &dest) {
+        out.println(&format!("'{}' is already placed in the workspace", src.display()));
+        return Ok(Outcome::Skipped);
+      }
+      return Err(io::Error::new(io::ErrorKind::AlreadyExists,
+                                format!("the target has already existed: {}", dest.display()))
+        .into());
+    }
+
+    let action = if link { "link" } else { "move" };
+    if dry_run {
+      out.println(&format!("would {} '{}' to '{}'", action, src.display(), dest.display()));
+      return Ok(Outcome::Skipped);
+    }
+
+    out.println(&format!("{} '{}' to '{}'", action, src.display(), dest.display()));
+    if let Some(parent) = dest.parent() {
+      fs::create_dir_all(parent)?;
+    }
+    if link {
+      symlink_dir(&src, &dest)?;
+      Ok(Outcome::Linked)
+    } else {
+      move_dir(&src, &dest)?;
+      Ok(Outcome::Moved)
+    }
+  }
+
+  // delete the local repository, and the parent directories left empty.
+  // repositories with changes which are not pushed are kept unless `force` is given.
+  pub fn remove<P: AsRef<Path>>(&self, root: P, force: bool) -> Result<PathBuf, GhqError> {
+    let root = root.as_ref();
+    let dest = self.local_path(root);
+
+    if !force {
+      // bare repositories (cloned with `--bare`) have no working tree to inspect.
+      let bare = VCS::detect(&dest).is_none();
+      let mut reasons = match vcs::status_of(&dest)? {
+        Some(status) => unsafe_changes(&status),
+        None if bare => Vec::new(),
+        None => vec!["no status to inspect".to_owned()],
+      };
+      // commits ahead of the upstream are not enough, since branches may be never pushed.
+      match self.vcs().unpushed_commits(&dest) {
+        Ok(Some(0)) => (),
+        Ok(Some(n)) => reasons.push(format!("{} commits not pushed to any remote", n)),
+        _ if bare => {
+          reasons.push("a bare repository without remote branches to compare".to_owned())
+        }
+        _ => reasons.push("commits which may not be pushed".to_owned()),
+      }
+      if !reasons.is_empty() {
+        return Err(GhqError::Unsafe(self.full_name(), reasons));
+      }
+    }
+
+    if fs::symlink_metadata(&dest)?.file_type().is_symlink() {
+      // imported with `--link`; keep the linked directory.
+      fs::remove_file(&dest)?;
+    } else {
+      fs::remove_dir_all(&dest)?;
+    }
+
+    let mut dir = dest.parent();
+    while let Some(d) = dir {
+      if d == root || !d.starts_with(root) || fs::remove_dir(d).is_err() {
+        break;
+      }
+      dir = d.parent();
+    }
+
+    Ok(dest)
+  }
+
+  pub fn update<P: AsRef<Path>>(&self, root: P, out: &mut Output) -> Result<Outcome, GhqError> {
+    let dest = self.local_path(root);
+
+    out.println(&format!("update '{}'", dest.display()));
+    let before = self.vcs.revision(&dest)?;
+    self.vcs.update_repository(&dest, out)?;
+    let after = self.vcs.revision(&dest)?;
+
+    if before == after {
+      Ok(Outcome::UpToDate)
+    } else {
+      Ok(Outcome::Updated)
+    }
+  }
+}
+
+
+// describe the changes which would be lost by removing the repository.
+fn unsafe_changes(status: &vcs::Status) -> Vec<String> {
+  let mut reasons = Vec::new();
+  if status.changes() > 0 {
+    reasons.push(format!("{} uncommitted changes", status.changes()));
+  }
+  if status.untracked() > 0 {
+    reasons.push(format!("{} untracked files", status.untracked()));
+  }
+  if status.stash_count() > 0 {
+    reasons.push(format!("{} stashes", status.stash_count()));
+  }
+  if status.submodules() > 0 {
+    reasons.push(format!("{} modified submodules", status.submodules()));
+  }
+  reasons
+}
+
+
+// parse a remote URL, including scp-like syntax (`[user@]host:path`) of Git
+// and absolute paths of local repositories.
+pub fn parse_url(s: &str) -> Option<Url> {
+  if Path::new(s).is_absolute() {
+    return Url::from_file_path(s).ok();
+  }
+  if !s.contains("://") {
+    let re = Regex::new(r"^(?:(?P<user>[^@/:]+)@)?(?P<host>[^@/:]{2,}):(?P<path>.+)$").unwrap();
+    return re.captures(s).and_then(|caps| {
+      let user = caps.name("user").map(|u| format!("{}@", u)).unwrap_or_default();
+      // a relative path is relative to the home directory, which is `/~/` in SSH URLs of Git.
+      let path = caps.name("path").unwrap();
+      let path = match path.starts_with('/') {
+        true => path.to_owned(),
+        false => format!("/~/{}", path),
+      };
+      let url = format!("ssh://{}{}{}", user, caps.name("host").unwrap(), path);
+      Url::parse(&url).ok()
+    });
+  }
+
+  Url::parse(s).ok()
+}
+
+
+#[cfg(unix)]
+fn symlink_dir(src: &Path, dest: &Path) -> io::Result<()> {
+  ::std::os::unix::fs::symlink(src, dest)
+}
+
+#[cfg(windows)]
+fn symlink_dir(src: &Path, dest: &Path) -> io::Result<()> {
+  ::std::os::windows::fs::symlink_dir(src, dest)
+}
+
+// `fs::rename` cannot move directories across filesystems (e.g. from `/tmp` to the home
+// directory), so the directory is copied and then removed in that case.
+fn move_dir(src: &Path, dest: &Path) -> io::Result<()> {
+  match fs::rename(src, dest) {
+    Err(ref err) if is_cross_device(err) => {
+      if let Err(err) = copy_dir(src, dest) {
+        let _ = fs::remove_dir_all(dest);
+        return Err(err);
+      }
+      fs::remove_dir_all(src)
+    }
+    r => r,
+  }
+}
+
+// EXDEV
+#[cfg(unix)]
+fn is_cross_device(err: &io::Error) -> bool {
+  err.raw_os_error() == Some(18)
+}
+
+// ERROR_NOT_SAME_DEVICE
+#[cfg(windows)]
+fn is_cross_device(err: &io::Error) -> bool {
+  err.raw_os_error() == Some(17)
+}
+
+// copy the directory recursively, keeping symbolic links and permissions.
+fn copy_dir(src: &Path, dest: &Path) -> io::Result<()> {
+  fs::create_dir(dest)?;
+  for entry in fs::read_dir(src)? {
+    let entry = entry?;
+    let (from, to) = (entry.path(), dest.join(entry.file_name()));
+    let file_type = entry.file_type()?;
+    if file_type.is_symlink() {
+      copy_symlink(&from, &to)?;
+    } else if file_type.is_dir() {
+      copy_dir(&from, &to)?;
+    } else {
+      fs::copy(&from, &to)?;
+    }
+  }
+  fs::set_permissions(dest, fs::metadata(src)?.permissions())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> io::Result<()> {
+  ::std::os::unix::fs::symlink(fs::read_link(src)?, dest)
+}
 
-    Ok(())
+#[cfg(windows)]
+fn copy_symlink(src: &Path, dest: &Path) -> io::Result<()> {
+  let target = fs::read_link(src)?;
+  if fs::metadata(src).map(|m| m.is_dir()).unwrap_or(false) {
+    ::std::os::windows::fs::symlink_dir(target, dest)
+  } else {
+    ::std::os::windows::fs::symlink_file(target, dest)
+  }
+}
+
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+  Cloned,
+  Updated,
+  UpToDate,
+  Moved,
+  Linked,
+  Skipped,
+}
+
+impl fmt::Display for Outcome {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let s = match *self {
+      Outcome::Cloned => "cloned",
+      Outcome::Updated => "updated",
+      Outcome::UpToDate => "up to date",
+      Outcome::Moved => "moved",
+      Outcome::Linked => "linked",
+      Outcome::Skipped => "skipped",
+    };
+    f.write_str(s)
   }
 }
 
@@ -116,10 +458,11 @@ impl Repository {
 #[cfg(test)]
 mod test_from_local {
   use super::Repository;
+  use host::Hosts;
 
   #[test]
   fn case1() {
-    let repo = Repository::from_local("github.com/hoge/fuga").unwrap();
+    let repo = Repository::from_local("github.com/hoge/fuga", &Hosts::default()).unwrap();
     assert!(repo.url.is_none());
     assert_eq!(repo.host, "github.com");
     assert_eq!(repo.path, "hoge/fuga");
@@ -127,23 +470,33 @@ mod test_from_local {
 
   #[test]
   fn case2() {
-    let repo = Repository::from_local("gist.github.com/0bacdbefa19f").unwrap();
+    let repo = Repository::from_local("gist.github.com/0bacdbefa19f", &Hosts::default()).unwrap();
     assert!(repo.url.is_none());
     assert_eq!(repo.host, "gist.github.com");
     assert_eq!(repo.path, "0bacdbefa19f");
   }
+
+  #[test]
+  fn nested_groups() {
+    let hosts = Hosts::default();
+    let repo = Repository::from_local("gitlab.com/group/subgroup/fuga", &hosts).unwrap();
+    assert_eq!(repo.owner(), "group/subgroup");
+    assert_eq!(repo.name(), "fuga");
+    assert!(Repository::from_local("github.com/group/subgroup/fuga", &hosts).is_err());
+  }
 }
 
 
 #[cfg(test)]
 mod test_from_remote {
   use super::Repository;
+  use host::Hosts;
 
   macro_rules! def_test {
     ($name:ident, $s:expr, $url:expr, $host:expr, $path:expr) => {
       #[test]
       fn $name() {
-        let repo = Repository::from_remote($s).unwrap();
+        let repo = Repository::from_remote($s, &Hosts::default()).unwrap();
         assert_eq!(repo.url.unwrap().as_str(), $url);
         assert_eq!(repo.host, $host);
         assert_eq!(repo.path, $path);
@@ -175,9 +528,327 @@ mod test_from_remote {
             "gitlab.com",
             "funga-/pecopeco");
 
+  def_test!(scp_like,
+            "git@github.com:hoge/fuga.git",
+            "ssh://git@github.com/~/hoge/fuga.git",
+            "github.com",
+            "hoge/fuga");
+
+  def_test!(scp_like_without_user,
+            "example.com:hoge/fuga",
+            "ssh://example.com/~/hoge/fuga",
+            "example.com",
+            "hoge/fuga");
+
+  def_test!(scp_like_absolute,
+            "git@git.example.com:/srv/git/hoge/fuga.git",
+            "ssh://git@git.example.com/srv/git/hoge/fuga.git",
+            "git.example.com",
+            "srv/git/hoge/fuga");
+
+  def_test!(ssh_with_port,
+            "ssh://git@git.example.com:2222/hoge/fuga.git",
+            "ssh://git@git.example.com:2222/hoge/fuga.git",
+            "git.example.com",
+            "hoge/fuga");
+
+  def_test!(gist,
+            "gist.github.com/0bacdbefa19f",
+            "https://gist.github.com/0bacdbefa19f.git",
+            "gist.github.com",
+            "0bacdbefa19f");
+
+  def_test!(nested_groups,
+            "gitlab.com/group/subgroup/fuga",
+            "https://gitlab.com/group/subgroup/fuga.git",
+            "gitlab.com",
+            "group/subgroup/fuga");
+
+  def_test!(sourcehut,
+            "git.sr.ht/~hoge/fuga",
+            "https://git.sr.ht/~hoge/fuga",
+            "git.sr.ht",
+            "~hoge/fuga");
+
   def_test!(long_path,
             "github.com/hoge/fuga/foo/a/b/c",
             "https://github.com/hoge/fuga.git",
             "github.com",
             "hoge/fuga/foo/a/b/c");
 }
+
+
+#[cfg(test)]
+mod test_local_remote {
+  use std::fs::{self, File};
+  use std::io::Write;
+  use super::Repository;
+  use host::Hosts;
+  use testing::TempDir;
+  use vcs::VCS;
+
+  #[test]
+  fn from_config_files() {
+    let root = TempDir::new("local-remote");
+    let hosts = Hosts::default();
+
+    let git = root.join("github.com/hoge/fuga/.git");
+    fs::create_dir_all(&git).unwrap();
+    File::create(git.join("config"))
+      .unwrap()
+      .write_all(b"[core]\n\tbare = false\n\
+                   [remote \"origin\"]\n\turl = git@github.com:piyo/fuga.git\n")
+      .unwrap();
+    let repo = Repository::from_local("github.com/hoge/fuga", &hosts).unwrap();
+    assert_eq!(repo.remote_url(&root).unwrap().as_str(),
+               "ssh://git@github.com/~/piyo/fuga.git");
+    assert_eq!(repo.remote_mismatch(&root, &hosts).unwrap().full_name(),
+               "github.com/piyo/fuga");
+
+    let hg = root.join("bitbucket.org/hoge/piyo/.hg");
+    fs::create_dir_all(&hg).unwrap();
+    File::create(hg.join("hgrc"))
+      .unwrap()
+      .write_all(b"[paths]\ndefault = https://bitbucket.org/hoge/piyo\n")
+      .unwrap();
+    let repo = Repository::from_local("bitbucket.org/hoge/piyo", &hosts).unwrap().with_vcs(VCS::Hg);
+    assert_eq!(repo.remote_url(&root).unwrap().as_str(),
+               "https://bitbucket.org/hoge/piyo");
+    assert!(repo.remote_mismatch(&root, &hosts).is_none());
+  }
+}
+
+
+#[cfg(test)]
+mod test_remote_ssh {
+  use super::Repository;
+  use config::Config;
+  use host::Hosts;
+
+  #[test]
+  fn shorthand_for_all_hosts() {
+    let hosts = Hosts::from_config(&Config { ssh: Some(true), ..Config::default() });
+    let repo = Repository::from_remote("hoge/fuga", &hosts).unwrap();
+    assert_eq!(repo.url.unwrap().as_str(), "ssh://git@github.com/hoge/fuga.git");
+    assert_eq!(repo.host, "github.com");
+    assert_eq!(repo.path, "hoge/fuga");
+  }
+
+  #[test]
+  fn shorthand_for_configured_hosts() {
+    let hosts = Hosts::from_config(&Config {
+      ssh_hosts: Some(vec!["git.example.com".to_owned()]),
+      ..Config::default()
+    });
+    let repo = Repository::from_remote("git.example.com/hoge/fuga", &hosts).unwrap();
+    assert_eq!(repo.url.unwrap().as_str(), "ssh://git@git.example.com/hoge/fuga.git");
+    let repo = Repository::from_remote("hoge/fuga", &hosts).unwrap();
+    assert_eq!(repo.url.unwrap().as_str(), "https://github.com/hoge/fuga.git");
+  }
+}
+
+
+#[cfg(test)]
+mod test_remote_vcs {
+  use std::collections::BTreeMap;
+  use std::fs;
+  use url::Url;
+  use super::Repository;
+  use config::Config;
+  use host::Hosts;
+  use testing::{self, git, TempDir};
+  use vcs::VCS;
+
+  fn detect(s: &str) -> VCS {
+    Repository::from_remote(s, &Hosts::default()).unwrap().vcs
+  }
+
+  #[test]
+  fn from_scheme() {
+    assert_eq!(detect("svn://svn.example.com/project/trunk"), VCS::Svn);
+    assert_eq!(detect("svn+ssh://svn.example.com/project"), VCS::Svn);
+    assert_eq!(detect("git+ssh://example.com/hoge/fuga.git"), VCS::Git);
+    assert_eq!(detect("ssh://hg@bitbucket.org/hoge/fuga"), VCS::Hg);
+    assert_eq!(detect("https://github.com/hoge/fuga.git"), VCS::Git);
+  }
+
+  #[test]
+  fn from_prefix() {
+    let repo = Repository::from_remote("hg::https://hg.example.com/hoge/fuga", &Hosts::default())
+      .unwrap();
+    assert_eq!(repo.vcs, VCS::Hg);
+    assert_eq!(repo.url.unwrap().as_str(), "https://hg.example.com/hoge/fuga");
+    assert_eq!(repo.host, "hg.example.com");
+    assert_eq!(repo.path, "hoge/fuga");
+  }
+
+  #[test]
+  fn from_config() {
+    let mut hosts = BTreeMap::new();
+    hosts.insert("hg.mozilla.org".to_owned(), "hg".to_owned());
+    let hosts = Hosts::from_config(&Config { vcs: Some(hosts), ..Config::default() });
+
+    let repo = Repository::from_remote("https://hg.mozilla.org/mozilla-central", &hosts).unwrap();
+    assert_eq!(repo.vcs, VCS::Hg);
+    let repo = Repository::from_remote("https://github.com/hoge/fuga", &hosts).unwrap();
+    assert_eq!(repo.vcs, VCS::Git);
+  }
+
+  #[test]
+  fn from_local_repository() {
+    let dir = TempDir::new("remote-vcs");
+
+    if testing::available("git") {
+      assert!(git(&dir, &["init", "--quiet", "--bare", "fuga.git"]));
+      assert_eq!(detect(Url::from_file_path(dir.join("fuga.git")).unwrap().as_str()), VCS::Git);
+    }
+
+    let hg = dir.join("hg");
+    fs::create_dir_all(hg.join(".hg/store")).unwrap();
+    assert_eq!(detect(Url::from_file_path(&hg).unwrap().as_str()), VCS::Hg);
+
+    let svn = dir.join("svn");
+    fs::create_dir_all(svn.join("db")).unwrap();
+    fs::File::create(svn.join("format")).unwrap();
+    assert_eq!(detect(Url::from_file_path(&svn).unwrap().as_str()), VCS::Svn);
+
+    let repo = Repository::from_remote(Url::from_file_path(&svn).unwrap().as_str(),
+                                       &Hosts::default())
+      .unwrap();
+    assert_eq!(repo.host, "localhost");
+    assert_eq!(repo.path, svn.to_str().unwrap().trim_left_matches("/"));
+  }
+}
+
+
+#[cfg(test)]
+mod test_remove {
+  use std::fs::{self, File};
+  use super::Repository;
+  use error::GhqError;
+  use host::Hosts;
+  use testing::{self, git, TempDir};
+
+  #[test]
+  fn refuse_untracked_files() {
+    if !testing::available("git") {
+      return;
+    }
+    let root = TempDir::new("remove");
+    let repo = Repository::from_local("github.com/hoge/fuga", &Hosts::default()).unwrap();
+    let dest = repo.local_path(&root);
+    fs::create_dir_all(&dest).unwrap();
+    fs::create_dir_all(root.join("github.com/hoge/piyo")).unwrap();
+    assert!(git(&dest, &["init", "--quiet"]));
+    File::create(dest.join("README")).unwrap();
+
+    match repo.remove(&root, false) {
+      Err(GhqError::Unsafe(_, reasons)) => assert_eq!(reasons, vec!["1 untracked files"]),
+      _ => panic!("should be refused"),
+    }
+    assert!(dest.exists());
+
+    // `github.com/hoge` is kept since `piyo` remains.
+    assert_eq!(repo.remove(&root, true).unwrap(), dest);
+    assert!(!dest.exists());
+    assert!(root.join("github.com/hoge").exists());
+  }
+
+  #[test]
+  fn refuse_commits_without_upstream() {
+    if !testing::available("git") {
+      return;
+    }
+    let root = TempDir::new("remove-unpushed");
+    let upstream = root.join("upstream");
+    fs::create_dir_all(&upstream).unwrap();
+    assert!(git(&upstream, &["init", "--quiet"]));
+    assert!(git(&upstream, &["commit", "--quiet", "--allow-empty", "-m", "first"]));
+
+    let repo = Repository::from_local("github.com/hoge/fuga", &Hosts::default()).unwrap();
+    let dest = repo.local_path(&root);
+    fs::create_dir_all(dest.parent().unwrap()).unwrap();
+    assert!(git(&root, &["clone", "--quiet", "upstream", dest.to_str().unwrap()]));
+
+    // a branch which has never been pushed, while the checked out branch is up to date.
+    assert!(git(&dest, &["checkout", "--quiet", "-b", "topic"]));
+    assert!(git(&dest, &["commit", "--quiet", "--allow-empty", "-m", "second"]));
+    assert!(git(&dest, &["checkout", "--quiet", "-"]));
+    match repo.remove(&root, false) {
+      Err(GhqError::Unsafe(_, reasons)) => {
+        assert_eq!(reasons, vec!["1 commits not pushed to any remote"])
+      }
+      _ => panic!("should be refused"),
+    }
+
+    assert!(git(&dest, &["push", "--quiet", "origin", "topic"]));
+    assert!(git(&dest, &["fetch", "--quiet"]));
+    assert_eq!(repo.remove(&root, false).unwrap(), dest);
+  }
+
+  #[test]
+  fn bare_clones() {
+    if !testing::available("git") {
+      return;
+    }
+    let root = TempDir::new("remove-bare");
+    let upstream = root.join("upstream");
+    fs::create_dir_all(&upstream).unwrap();
+    assert!(git(&upstream, &["init", "--quiet"]));
+    assert!(git(&upstream, &["commit", "--quiet", "--allow-empty", "-m", "first"]));
+
+    let repo = Repository::from_local("github.com/hoge/fuga", &Hosts::default()).unwrap();
+    let dest = repo.local_path(&root);
+    fs::create_dir_all(dest.parent().unwrap()).unwrap();
+    assert!(git(&root, &["clone", "--quiet", "--bare", "upstream", dest.to_str().unwrap()]));
+    match repo.remove(&root, false) {
+      Err(GhqError::Unsafe(_, reasons)) => {
+        assert_eq!(reasons, vec!["a bare repository without remote branches to compare"])
+      }
+      _ => panic!("should be refused"),
+    }
+
+    // remote-tracking branches tell that all commits are pushed.
+    assert!(git(&dest,
+                &["config", "remote.origin.fetch", "+refs/heads/*:refs/remotes/origin/*"]));
+    assert!(git(&dest, &["fetch", "--quiet"]));
+    assert_eq!(repo.remove(&root, false).unwrap(), dest);
+  }
+}
+
+
+#[cfg(all(test, unix))]
+mod test_move_dir {
+  use std::fs::{self, File};
+  use std::io::{Read, Write};
+  use std::os::unix::fs::{symlink, PermissionsExt};
+  use super::{copy_dir, move_dir};
+  use testing::TempDir;
+
+  #[test]
+  fn copy_and_move() {
+    let dir = TempDir::new("move-dir");
+    let src = dir.join("src");
+    fs::create_dir_all(src.join(".git/objects")).unwrap();
+    File::create(src.join(".git/HEAD")).unwrap().write_all(b"ref: refs/heads/master\n").unwrap();
+    File::create(src.join("run.sh")).unwrap();
+    fs::set_permissions(src.join("run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    symlink("run.sh", src.join("link")).unwrap();
+
+    // the fallback for moves across filesystems.
+    let copied = dir.join("copied");
+    copy_dir(&src, &copied).unwrap();
+    let mut head = String::new();
+    File::open(copied.join(".git/HEAD")).unwrap().read_to_string(&mut head).unwrap();
+    assert_eq!(head, "ref: refs/heads/master\n");
+    assert!(copied.join(".git/objects").is_dir());
+    let mode = fs::metadata(copied.join("run.sh")).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o755);
+    assert_eq!(fs::read_link(copied.join("link")).unwrap().to_str(), Some("run.sh"));
+
+    let moved = dir.join("moved");
+    move_dir(&src, &moved).unwrap();
+    assert!(!src.exists());
+    assert!(moved.join(".git/HEAD").exists());
+  }
+}
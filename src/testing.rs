@@ -0,0 +1,105 @@
+// Helpers shared by the tests.
+
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Stdio};
+use std::thread;
+
+
+// a directory under the temporary directory of the system, removed with its content on drop.
+pub struct TempDir {
+  path: PathBuf,
+}
+
+impl TempDir {
+  pub fn new(name: &str) -> TempDir {
+    let path = env::temp_dir().join(format!("ghqrs-test-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    TempDir { path: path }
+  }
+
+  pub fn path(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Deref for TempDir {
+  type Target = Path;
+
+  fn deref(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl AsRef<Path> for TempDir {
+  fn as_ref(&self) -> &Path {
+    &self.path
+  }
+}
+
+impl Drop for TempDir {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.path);
+  }
+}
+
+
+// copy `tests/fixtures/<path>` into a temporary directory. `.git` and `.gitignore` cannot be
+// checked in as they are, so `dot-*` entries are renamed to `.*`.
+pub fn fixture(path: &str) -> TempDir {
+  fn copy_dir(src: &Path, dest: &Path) {
+    fs::create_dir_all(dest).unwrap();
+    for entry in fs::read_dir(src).unwrap() {
+      let entry = entry.unwrap();
+      let name = entry.file_name().to_string_lossy().into_owned();
+      let target = match name.starts_with("dot-") {
+        true => dest.join(format!(".{}", &name[4..])),
+        false => dest.join(&name),
+      };
+      if entry.file_type().unwrap().is_dir() {
+        copy_dir(&entry.path(), &target);
+      } else {
+        fs::copy(entry.path(), &target).unwrap();
+      }
+    }
+  }
+
+  let dir = TempDir::new(&format!("fixture-{}", path.replace('/', "-")));
+  copy_dir(&Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(path), &dir);
+  dir
+}
+
+
+// check if `command` can be run, and report the test as skipped otherwise. the report is written
+// to stderr directly, since the test harness captures `eprintln!`.
+pub fn available(command: &str) -> bool {
+  let found = Command::new(command)
+    .arg("--version")
+    .stdout(Stdio::null())
+    .stderr(Stdio::null())
+    .status()
+    .map(|st| st.success())
+    .unwrap_or(false);
+  if !found {
+    let _ = writeln!(&mut io::stderr(),
+                     "test {} skipped: `{}` is not available",
+                     thread::current().name().unwrap_or("<unnamed>"),
+                     command);
+  }
+  found
+}
+
+// run git in `dir` with a fixed identity, and check if it succeeded.
+pub fn git(dir: &Path, args: &[&str]) -> bool {
+  Command::new("git")
+    .args(&["-c", "user.name=ghqrs", "-c", "user.email=ghqrs@example.com"])
+    .args(args)
+    .current_dir(dir)
+    .output()
+    .map(|out| out.status.success())
+    .unwrap_or(false)
+}
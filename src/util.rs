@@ -1,6 +1,6 @@
 use std::fs::File;
 use std::io::{self, Read};
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
 use std::process::{Command, Stdio};
 
 pub trait SplitEOL {
@@ -17,16 +17,22 @@ impl SplitEOL for String {
   }
 }
 
-pub fn communicate(name: &str, args: &[&str]) -> Result<(String, String, i32), io::Error> {
-  use std::process::*;
-
-  let output = Command::new(name).args(args)
+pub fn communicate(name: &str,
+                   args: &[&str],
+                   curr_dir: Option<&Path>)
+                   -> Result<(String, String, i32), io::Error> {
+  let mut command = Command::new(name);
+  command.args(args)
     .stdin(Stdio::null())
     .stdout(Stdio::piped())
-    .stderr(Stdio::piped())
-    .output()?;
+    .stderr(Stdio::piped());
+  if let Some(curr_dir) = curr_dir {
+    command.current_dir(curr_dir);
+  }
+
+  let output = command.output()?;
   let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
-  let stderr = String::from_utf8_lossy(&output.stdout).into_owned();
+  let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
   let status = output.status
     .code()
     .ok_or(io::Error::new(io::ErrorKind::Other,
@@ -35,8 +41,11 @@ pub fn communicate(name: &str, args: &[&str]) -> Result<(String, String, i32), i
   Ok((stdout, stderr, status))
 }
 
-pub fn get_lines(name: &str, args: &[&str]) -> Result<Vec<String>, io::Error> {
-  communicate(name, args).map(|(stdout, _, _)| stdout.split_eol())
+pub fn get_lines(name: &str,
+                 args: &[&str],
+                 curr_dir: Option<&Path>)
+                 -> Result<Vec<String>, io::Error> {
+  communicate(name, args, curr_dir).map(|(stdout, _, _)| stdout.split_eol())
 }
 
 pub fn read_content<P: AsRef<Path>>(path: P) -> io::Result<String> {
@@ -60,3 +69,152 @@ pub fn wait_exec(cmd: &str, args: &[&str], curr_dir: Option<&Path>) -> Result<i3
   child.wait()
     .and_then(|st| st.code().ok_or(io::Error::new(io::ErrorKind::Other, "")))
 }
+
+pub fn same_path(a: &Path, b: &Path) -> bool {
+  match (a.canonicalize(), b.canonicalize()) {
+    (Ok(a), Ok(b)) => a == b,
+    _ => a == b,
+  }
+}
+
+
+// Destination of the outputs from child processes and progress messages.
+pub enum Output {
+  // write directly to stdout/stderr of this process.
+  Inherit,
+  // accumulate into the buffer.
+  Capture(String),
+}
+
+impl Output {
+  pub fn println(&mut self, msg: &str) {
+    match *self {
+      Output::Inherit => println!("{}", msg),
+      Output::Capture(ref mut buf) => {
+        buf.push_str(msg);
+        buf.push('\n');
+      }
+    }
+  }
+
+  pub fn exec(&mut self, cmd: &str, args: &[&str], curr_dir: Option<&Path>) -> io::Result<i32> {
+    let buf = match *self {
+      Output::Inherit => return wait_exec(cmd, args, curr_dir),
+      Output::Capture(ref mut buf) => buf,
+    };
+
+    let mut command = Command::new(cmd);
+    command.args(args)
+      .stdin(Stdio::null())
+      .stdout(Stdio::piped())
+      .stderr(Stdio::piped());
+    if let Some(curr_dir) = curr_dir {
+      command.current_dir(curr_dir);
+    }
+
+    let output = command.output()?;
+    buf.push_str(&String::from_utf8_lossy(&output.stdout));
+    buf.push_str(&String::from_utf8_lossy(&output.stderr));
+    output.status
+      .code()
+      .ok_or(io::Error::new(io::ErrorKind::Other,
+                            "The process was terminated by a signal"))
+  }
+
+  pub fn captured(&self) -> Option<&str> {
+    match *self {
+      Output::Inherit => None,
+      Output::Capture(ref buf) => Some(buf),
+    }
+  }
+}
+
+
+// look up a value in INI-like files such as `.git/config` and `.hg/hgrc`.
+// `section` is the whole content of the header, e.g. `remote "origin"` or `paths`.
+pub fn ini_value(content: &str, section: &str, key: &str) -> Option<String> {
+  ini_entry(content, section, key).and_then(|value| value)
+}
+
+// a boolean in the Git config, where a key without `=` is true.
+pub fn ini_bool(content: &str, section: &str, key: &str) -> Option<bool> {
+  match ini_entry(content, section, key)? {
+    None => Some(true),
+    Some(value) => {
+      match value.to_lowercase().as_str() {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" | "" => Some(false),
+        value => value.parse::<i64>().ok().map(|n| n != 0),
+      }
+    }
+  }
+}
+
+// the value of `key`, or `Some(None)` if the key has no value.
+fn ini_entry(content: &str, section: &str, key: &str) -> Option<Option<String>> {
+  let mut current = None;
+  for line in content.lines().map(|line| line.trim()) {
+    if line.starts_with('#') || line.starts_with(';') {
+      continue;
+    }
+    if line.starts_with('[') && line.ends_with(']') {
+      current = Some(line[1..line.len() - 1].trim());
+      continue;
+    }
+    if current != Some(section) {
+      continue;
+    }
+    let mut kv = line.splitn(2, '=');
+    match (kv.next(), kv.next()) {
+      (Some(k), Some(v)) if k.trim().eq_ignore_ascii_case(key) => {
+        return Some(Some(v.trim().to_owned()))
+      }
+      (Some(k), None) if k.eq_ignore_ascii_case(key) => return Some(None),
+      _ => (),
+    }
+  }
+  None
+}
+
+// resolve `.` and `..` in the path without touching the filesystem.
+pub fn normalize_path(path: &Path) -> PathBuf {
+  let mut normalized = PathBuf::new();
+  for component in path.components() {
+    match component {
+      Component::CurDir => (),
+      Component::ParentDir if normalized.file_name().is_some() => {
+        normalized.pop();
+      }
+      component => normalized.push(component.as_os_str()),
+    }
+  }
+  normalized
+}
+
+
+#[cfg(test)]
+mod test_ini {
+  use std::path::{Path, PathBuf};
+  use super::{ini_bool, ini_value, normalize_path};
+
+  #[test]
+  fn booleans() {
+    let config = "[core]\n\tbare\n\tsparseCheckout = yes\n\tfilemode = off\n\tsymlinks = 1\n\t\
+                  ignorecase =\n\tpreloadIndex = maybe\n";
+    assert_eq!(ini_bool(config, "core", "bare"), Some(true));
+    assert_eq!(ini_bool(config, "core", "sparsecheckout"), Some(true));
+    assert_eq!(ini_bool(config, "core", "fileMode"), Some(false));
+    assert_eq!(ini_bool(config, "core", "symlinks"), Some(true));
+    assert_eq!(ini_bool(config, "core", "ignoreCase"), Some(false));
+    assert_eq!(ini_bool(config, "core", "preloadIndex"), None);
+    assert_eq!(ini_bool(config, "core", "logAllRefUpdates"), None);
+    assert_eq!(ini_value(config, "core", "bare"), None);
+  }
+
+  #[test]
+  fn normalize() {
+    assert_eq!(normalize_path(Path::new("/a/sub/../.git/./modules/sub")),
+               PathBuf::from("/a/.git/modules/sub"));
+    assert_eq!(normalize_path(Path::new("../a/../b")), PathBuf::from("../b"));
+  }
+}
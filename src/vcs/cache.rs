@@ -0,0 +1,317 @@
+// Cache of the status of repositories, which lets `vcs_info` skip running commands while the
+// repository is unchanged.
+//
+// each entry is a JSON file, valid while the modified times of the files such as `.git/index`,
+// `.git/HEAD` and the refs of the branch and its upstream stay the same. changes in the working
+// tree which don't touch them (e.g. editing a tracked file) are noticed only after the entry
+// expires, in `DEFAULT_TTL` seconds unless `cache_ttl` is configured.
+
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use rustc_serialize::Decodable;
+use rustc_serialize::json::{self, Json};
+use config::expand_full;
+use vcs::{self, git, hg, svn, Status, VCS};
+
+
+pub const DEFAULT_TTL: u64 = 60;
+
+// seconds after which a lock is assumed to be left by a worker which was killed.
+const LOCK_TIMEOUT: u64 = 600;
+
+pub struct StatusCache {
+  dir: PathBuf,
+  // seconds until entries expire
+  ttl: u64,
+}
+
+#[derive(RustcEncodable)]
+struct Entry<'a> {
+  path: String,
+  vcs: VCS,
+  key: String,
+  created: u64,
+  status: &'a Status,
+}
+
+// the status passed from the worker of `vcs_info` to the prompt.
+#[derive(RustcEncodable)]
+struct Message<'a> {
+  vcs: VCS,
+  status: &'a Status,
+}
+
+// held while a worker collects the status of a repository, and released on drop.
+pub struct Lock {
+  path: PathBuf,
+}
+
+impl Drop for Lock {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+impl StatusCache {
+  pub fn new<P: Into<PathBuf>>(dir: P, ttl: u64) -> StatusCache {
+    StatusCache {
+      dir: dir.into(),
+      ttl: ttl,
+    }
+  }
+
+  // `$XDG_CACHE_HOME/ghqrs/status`, or `~/.cache/ghqrs/status`.
+  pub fn default_dir() -> Option<PathBuf> {
+    let base = match expand_full("$XDG_CACHE_HOME") {
+      Ok(ref dir) if Path::new(dir).is_absolute() => dir.clone(),
+      _ => expand_full("~/.cache").ok()?,
+    };
+    Some(Path::new(&base).join("ghqrs/status"))
+  }
+
+  // the cached status of the repository at `root`, if it is still valid.
+  pub fn load(&self, root: &Path) -> Option<Status> {
+    let vcs = VCS::detect(root)?;
+    let mut content = String::new();
+    File::open(self.entry_path(root)).ok()?.read_to_string(&mut content).ok()?;
+
+    let entry = Json::from_str(&content).ok()?;
+    let entry = entry.as_object()?;
+    if entry.get("path")?.as_string()? != root.to_string_lossy() ||
+       entry.get("vcs")?.as_string()? != vcs.to_string() ||
+       entry.get("key")?.as_string()? != cache_key(root) {
+      return None;
+    }
+    let created = entry.get("created")?.as_u64()?;
+    if now().saturating_sub(created) >= self.ttl {
+      return None;
+    }
+    decode_as(vcs, entry.get("status")?)
+  }
+
+  pub fn store(&self, root: &Path, status: &Status) -> io::Result<()> {
+    let entry = Entry {
+      path: root.to_string_lossy().into_owned(),
+      vcs: vcs_of(status),
+      key: cache_key(root),
+      created: now(),
+      status: status,
+    };
+    let content = json::encode(&entry).map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+
+    // write to a temporary file first, since other shells may read the entry at the same time.
+    fs::create_dir_all(&self.dir)?;
+    let path = self.entry_path(root);
+    let temp = path.with_extension(format!("{}.tmp", ::std::process::id()));
+    File::create(&temp)?.write_all(content.as_bytes())?;
+    fs::rename(&temp, &path)
+  }
+
+  // take the lock of the repository at `root`, or `None` while another worker holds it.
+  pub fn lock(&self, root: &Path) -> io::Result<Option<Lock>> {
+    fs::create_dir_all(&self.dir)?;
+    let path = self.entry_path(root).with_extension("lock");
+    for _ in 0..2 {
+      match OpenOptions::new().write(true).create_new(true).open(&path) {
+        Ok(_) => return Ok(Some(Lock { path: path })),
+        Err(ref err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+        Err(err) => return Err(err),
+      }
+      let age = fs::metadata(&path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.elapsed().ok())
+        .map(|d| d.as_secs());
+      match age {
+        Some(age) if age < LOCK_TIMEOUT => return Ok(None),
+        _ => {
+          let _ = fs::remove_file(&path);
+        }
+      }
+    }
+    Ok(None)
+  }
+
+  fn entry_path(&self, root: &Path) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    root.hash(&mut hasher);
+    self.dir.join(format!("{:016x}.json", hasher.finish()))
+  }
+}
+
+// the status as JSON, which `decode_status` reads back.
+pub fn encode_status(status: &Status) -> io::Result<String> {
+  let message = Message {
+    vcs: vcs_of(status),
+    status: status,
+  };
+  json::encode(&message).map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+}
+
+pub fn decode_status(content: &str) -> Option<Status> {
+  let message = Json::from_str(content).ok()?;
+  let message = message.as_object()?;
+  let vcs = message.get("vcs")?.as_string()?.parse().ok()?;
+  decode_as(vcs, message.get("status")?)
+}
+
+fn decode_as(vcs: VCS, status: &Json) -> Option<Status> {
+  let mut d = json::Decoder::new(status.clone());
+  match vcs {
+    VCS::Git => git::Status::decode(&mut d).ok().map(Status::Git),
+    VCS::Hg => hg::Status::decode(&mut d).ok().map(Status::Hg),
+    VCS::Svn => svn::Status::decode(&mut d).ok().map(Status::Svn),
+    VCS::Darcs => None,
+  }
+}
+
+fn vcs_of(status: &Status) -> VCS {
+  match *status {
+    Status::Git(_) => VCS::Git,
+    Status::Hg(_) => VCS::Hg,
+    Status::Svn(_) => VCS::Svn,
+  }
+}
+
+// the modified times of the key files, e.g. `1500000000.123456789,-`.
+fn cache_key(root: &Path) -> String {
+  vcs::key_files(root)
+    .into_iter()
+    .map(|path| {
+      fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| format!("{}.{:09}", d.as_secs(), d.subsec_nanos()))
+        .unwrap_or("-".to_owned())
+    })
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+fn now() -> u64 {
+  SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+
+#[cfg(test)]
+mod test_cache {
+  use std::fs::{self, File, OpenOptions};
+  use std::io::Write;
+  use std::path::Path;
+  use filetime::{self, FileTime};
+  use super::{decode_status, encode_status, DEFAULT_TTL, StatusCache};
+  use testing::TempDir;
+  use vcs::{git, Status};
+
+  fn touch(path: &Path, time: i64) {
+    OpenOptions::new().create(true).write(true).open(path).unwrap();
+    filetime::set_file_mtime(path, FileTime::from_unix_time(time, 0)).unwrap();
+  }
+
+  fn setup(name: &str) -> TempDir {
+    let dir = TempDir::new(&format!("cache-{}", name));
+    fs::create_dir_all(dir.join("repo/.git")).unwrap();
+    touch(&dir.join("repo/.git/HEAD"), 1500000000);
+    touch(&dir.join("repo/.git/index"), 1500000000);
+    dir
+  }
+
+  fn status() -> Status {
+    Status::Git(git::Status {
+      branch: "master".to_owned(),
+      index: Some(git::DiffInfo { added: 2, ..Default::default() }),
+      untracked: 1,
+      ..Default::default()
+    })
+  }
+
+  #[test]
+  fn invalidated_by_key_files() {
+    let dir = setup("key");
+    let repo = dir.join("repo");
+    let cache = StatusCache::new(dir.join("cache"), DEFAULT_TTL);
+    assert!(cache.load(&repo).is_none());
+
+    cache.store(&repo, &status()).unwrap();
+    let cached = cache.load(&repo).unwrap();
+    assert_eq!(cached.branch(), "master");
+    assert_eq!(cached.changes(), 2);
+    assert_eq!(cached.untracked(), 1);
+
+    // another repository doesn't share the entry.
+    fs::create_dir_all(dir.join("other/.git")).unwrap();
+    assert!(cache.load(&dir.join("other")).is_none());
+
+    touch(&repo.join(".git/index"), 1500000001);
+    assert!(cache.load(&repo).is_none());
+  }
+
+  #[test]
+  fn invalidated_by_upstream() {
+    let dir = setup("upstream");
+    let repo = dir.join("repo");
+    File::create(repo.join(".git/HEAD")).unwrap().write_all(b"ref: refs/heads/master\n").unwrap();
+    File::create(repo.join(".git/config"))
+      .unwrap()
+      .write_all(b"[branch \"master\"]\n\tremote = origin\n\tmerge = refs/heads/master\n")
+      .unwrap();
+    fs::create_dir_all(repo.join(".git/refs/remotes/origin")).unwrap();
+    touch(&repo.join(".git/HEAD"), 1500000000);
+    touch(&repo.join(".git/refs/remotes/origin/master"), 1500000000);
+    let cache = StatusCache::new(dir.join("cache"), DEFAULT_TTL);
+
+    cache.store(&repo, &status()).unwrap();
+    assert!(cache.load(&repo).is_some());
+    touch(&repo.join(".git/refs/remotes/origin/master"), 1500000001);
+    assert!(cache.load(&repo).is_none());
+
+    cache.store(&repo, &status()).unwrap();
+    assert!(cache.load(&repo).is_some());
+    touch(&repo.join(".git/FETCH_HEAD"), 1500000000);
+    assert!(cache.load(&repo).is_none());
+  }
+
+  #[test]
+  fn expired() {
+    let dir = setup("ttl");
+    let repo = dir.join("repo");
+    StatusCache::new(dir.join("cache"), DEFAULT_TTL).store(&repo, &status()).unwrap();
+    assert!(StatusCache::new(dir.join("cache"), 60).load(&repo).is_some());
+    assert!(StatusCache::new(dir.join("cache"), 0).load(&repo).is_none());
+  }
+
+  #[test]
+  fn encode_and_decode() {
+    let content = encode_status(&status()).unwrap();
+    let decoded = decode_status(&content).unwrap();
+    assert_eq!(decoded.branch(), "master");
+    assert_eq!(decoded.changes(), 2);
+    assert!(decode_status("").is_none());
+  }
+
+  #[test]
+  fn lock() {
+    let dir = setup("lock");
+    let repo = dir.join("repo");
+    let cache = StatusCache::new(dir.join("cache"), DEFAULT_TTL);
+
+    let lock = cache.lock(&repo).unwrap();
+    assert!(lock.is_some());
+    assert!(cache.lock(&repo).unwrap().is_none());
+    assert!(cache.lock(&dir.join("other")).unwrap().is_some());
+    drop(lock);
+    assert!(cache.lock(&repo).unwrap().is_some());
+
+    // a lock left by a killed worker is taken over.
+    let lock = cache.lock(&repo).unwrap().unwrap();
+    let path = lock.path.clone();
+    ::std::mem::forget(lock);
+    touch(&path, 1500000000);
+    assert!(cache.lock(&repo).unwrap().is_some());
+  }
+}
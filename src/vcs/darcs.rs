@@ -0,0 +1,43 @@
+use std::io;
+use std::path::Path;
+use url::Url;
+
+use util::*;
+use vcs::CloneOptions;
+
+pub fn get(url: &Url,
+           dest: &Path,
+           opts: &CloneOptions,
+           out: &mut Output)
+           -> Result<i32, io::Error> {
+  let mut args = vec!["get", url.as_str(), dest.to_str().unwrap()];
+  if opts.bare {
+    args.push("--no-working-dir");
+  }
+
+  out.exec("darcs", args.as_slice(), None)
+}
+
+pub fn pull(dest: &Path, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("darcs", &["pull", "--all"], Some(dest))
+}
+
+pub fn revision(dest: &Path) -> Result<Option<String>, io::Error> {
+  get_lines("darcs",
+            &["log", "--last", "1", "--repodir", dest.to_str().unwrap()],
+            None)
+    .map(|lines| {
+      lines.into_iter()
+        .find(|line| line.starts_with("patch "))
+        .map(|line| line.replace("patch ", ""))
+    })
+}
+
+// the default remote repository, recorded by `darcs get`.
+pub fn remote_url(dest: &Path) -> Result<Option<String>, io::Error> {
+  let prefs = dest.join("_darcs/prefs/defaultrepo");
+  if !prefs.is_file() {
+    return Ok(None);
+  }
+  read_content(prefs).map(|content| content.lines().next().map(ToOwned::to_owned))
+}
@@ -1,33 +1,40 @@
-use std::env;
 use std::io;
 use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use regex::Regex;
 use url::Url;
 
-use vcs::Prompt;
+use prompt::{Fields, Symbols};
+use vcs::CloneOptions;
 use util::*;
 
-#[derive(Default)]
+#[derive(Default, RustcEncodable, RustcDecodable)]
 pub struct Status {
-  branch: String,
-  ahead_by: usize,
-  behind_by: usize,
-  upstream: String,
-  index: Option<DiffInfo>,
-  working: Option<DiffInfo>,
-  untracked: usize,
-  stash_count: usize,
+  pub branch: String,
+  pub ahead_by: usize,
+  pub behind_by: usize,
+  pub upstream: String,
+  pub index: Option<DiffInfo>,
+  pub working: Option<DiffInfo>,
+  pub untracked: usize,
+  pub stash_count: usize,
+  // the operation in progress, e.g. `REBASE-i` or `MERGING`
+  pub operation: Option<String>,
+  // the number of submodules which have changes or commits not recorded in the index
+  pub submodules: usize,
+  // the working tree is added by `git worktree add`
+  pub linked_worktree: bool,
+  pub sparse: bool,
 }
 
-#[derive(Default)]
-struct DiffInfo {
-  added: usize,
-  modified: usize,
-  renamed: usize,
-  copied: usize,
-  deleted: usize,
-  unmerged: usize,
+#[derive(Default, RustcEncodable, RustcDecodable)]
+pub struct DiffInfo {
+  pub added: usize,
+  pub modified: usize,
+  pub renamed: usize,
+  pub copied: usize,
+  pub deleted: usize,
+  pub unmerged: usize,
 }
 
 #[allow(dead_code)]
@@ -40,7 +47,57 @@ enum DescribeStyle {
 }
 
 fn get_git_dir(wd: &Path) -> Option<PathBuf> {
-  get_local_or_parent_path(wd, ".git")
+  get_local_or_parent_path(wd, ".git").and_then(|dot_git| resolve_git_dir(&dot_git))
+}
+
+// the Git directory of `.git`, which is either the directory itself or a file which points to
+// another place, e.g. `gitdir: ../.git/modules/sub` of submodules.
+pub fn resolve_git_dir(dot_git: &Path) -> Option<PathBuf> {
+  if dot_git.is_dir() {
+    return Some(dot_git.to_path_buf());
+  }
+  let content = read_content(dot_git).ok()?;
+  if !content.starts_with("gitdir:") {
+    return None;
+  }
+  let base = dot_git.parent().unwrap_or(Path::new(""));
+  Some(normalize_path(&base.join(content["gitdir:".len()..].trim())))
+}
+
+// the directory shared by linked worktrees, which contains refs, objects and the config.
+pub fn get_common_dir(git_dir: &Path) -> PathBuf {
+  match read_content(git_dir.join("commondir")) {
+    Ok(ref common) if common != "" => git_dir.join(common),
+    _ => git_dir.to_path_buf(),
+  }
+}
+
+pub fn is_linked_worktree(git_dir: &Path) -> bool {
+  git_dir.join("commondir").is_file()
+}
+
+// whether `core.sparseCheckout` is enabled for the working tree.
+pub fn is_sparse(git_dir: &Path) -> bool {
+  [git_dir.join("config.worktree"), get_common_dir(git_dir).join("config")]
+    .iter()
+    .filter_map(|path| read_content(path).ok())
+    .filter_map(|config| ini_bool(&config, "core", "sparseCheckout"))
+    .next()
+    .unwrap_or(false)
+}
+
+// paths of the submodules listed in `.gitmodules`.
+fn get_submodule_paths(worktree: &Path) -> Vec<String> {
+  let content = read_content(worktree.join(".gitmodules")).unwrap_or_default();
+  content.lines()
+    .filter_map(|line| {
+      let mut kv = line.splitn(2, '=');
+      match (kv.next(), kv.next()) {
+        (Some(k), Some(v)) if k.trim() == "path" => Some(v.trim().trim_matches('"').to_owned()),
+        _ => None,
+      }
+    })
+    .collect()
 }
 
 fn get_local_or_parent_path(wd: &Path, path: &str) -> Option<PathBuf> {
@@ -53,46 +110,24 @@ fn get_local_or_parent_path(wd: &Path, path: &str) -> Option<PathBuf> {
   }
 }
 
-fn get_branch() -> io::Result<String> {
-  let git_dir = if let Some(git_dir) = get_git_dir(&env::current_dir().unwrap()) {
+fn get_branch(wd: &Path) -> io::Result<String> {
+  let git_dir = if let Some(git_dir) = get_git_dir(wd) {
     git_dir
   } else {
     return Ok(String::new());
   };
 
-  let mut r = String::new();
+  let r = get_operation(&git_dir).map(|op| format!("|{}", op)).unwrap_or_default();
   let mut b;
   let mut c = String::new();
 
-  if git_dir.join("rebase-merge/interactive").exists() {
-    // interactive rebase
-    r = "|REBASE-i".to_owned();
-    b = read_content(git_dir.join("rebase-merge/head-name"))?;
-
-  } else if git_dir.join("rebase-merge").exists() {
+  if git_dir.join("rebase-merge").exists() {
     // rebase-merge
-    r = "|REBASE-m".to_owned();
     b = read_content(git_dir.join("rebase-merge/head-name"))?;
 
   } else {
-    if git_dir.join("rebase-apply").exists() {
-      if git_dir.join("rebase-apply/rebasing").exists() {
-        r = "|REBASE".to_owned();
-      } else if git_dir.join("rebase-apply/applying").exists() {
-        r = "|AM".to_owned();
-      } else {
-        r = "|AM/REBASE".to_owned();
-      }
-    } else if git_dir.join("MERGE_HEAD").exists() {
-      r = "|MERGING".to_owned();
-    } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
-      r = "|CHERRY-PICKING".to_owned();
-    } else if git_dir.join("BISECT_LOG").exists() {
-      r = "|BISECTING".to_owned();
-    }
-
     // trying symbolic-ref
-    let _ref = get_lines("git", &["symbolic-ref", "HEAD", "-q"])
+    let _ref = get_lines("git", &["symbolic-ref", "HEAD", "-q"], Some(wd))
       ?
       .into_iter()
       .next();
@@ -100,18 +135,18 @@ fn get_branch() -> io::Result<String> {
       b = br;
     } else {
       // get tag or SHA1-hash
-      let hash = get_tag_or_hash(&git_dir, DescribeStyle::Default)?;
+      let hash = get_tag_or_hash(wd, &git_dir, DescribeStyle::Default)?;
       b = format!("({})", hash);
     }
   }
 
   // inside Git directory?
-  if get_lines("git", &["rev-parse", "--is-inside-git-dir"])
+  if get_lines("git", &["rev-parse", "--is-inside-git-dir"], Some(wd))
     ?
     .into_iter()
     .next()
     .unwrap() == "true" {
-    if get_lines("git", &["rev-parse", "--is-bare-repository"])
+    if get_lines("git", &["rev-parse", "--is-bare-repository"], Some(wd))
       ?
       .into_iter()
       .next()
@@ -126,14 +161,39 @@ fn get_branch() -> io::Result<String> {
 }
 
 
-fn get_tag_or_hash(git_dir: &Path, style: DescribeStyle) -> io::Result<String> {
+// the operation in progress, such as rebase and merge.
+pub fn get_operation(git_dir: &Path) -> Option<&'static str> {
+  if git_dir.join("rebase-merge/interactive").exists() {
+    // interactive rebase
+    Some("REBASE-i")
+  } else if git_dir.join("rebase-merge").exists() {
+    Some("REBASE-m")
+  } else if git_dir.join("rebase-apply/rebasing").exists() {
+    Some("REBASE")
+  } else if git_dir.join("rebase-apply/applying").exists() {
+    Some("AM")
+  } else if git_dir.join("rebase-apply").exists() {
+    Some("AM/REBASE")
+  } else if git_dir.join("MERGE_HEAD").exists() {
+    Some("MERGING")
+  } else if git_dir.join("CHERRY_PICK_HEAD").exists() {
+    Some("CHERRY-PICKING")
+  } else if git_dir.join("BISECT_LOG").exists() {
+    Some("BISECTING")
+  } else {
+    None
+  }
+}
+
+fn get_tag_or_hash(wd: &Path, git_dir: &Path, style: DescribeStyle) -> io::Result<String> {
   // trying describe
-  let describe = match style {
-    DescribeStyle::Contains => get_lines("git", &["describe", "--contains", "HEAD"])?,
-    DescribeStyle::Branch => get_lines("git", &["describe", "--contains", "--all", "HEAD"])?,
-    DescribeStyle::Describe => get_lines("git", &["describe", "HEAD"])?,
-    DescribeStyle::Default => get_lines("git", &["tag", "--points-at", "HEAD"])?,
+  let args: &[&str] = match style {
+    DescribeStyle::Contains => &["describe", "--contains", "HEAD"],
+    DescribeStyle::Branch => &["describe", "--contains", "--all", "HEAD"],
+    DescribeStyle::Describe => &["describe", "HEAD"],
+    DescribeStyle::Default => &["tag", "--points-at", "HEAD"],
   };
+  let describe = get_lines("git", args, Some(wd))?;
   if let Some(b) = describe.into_iter().next() {
     return Ok(b);
   }
@@ -145,7 +205,7 @@ fn get_tag_or_hash(git_dir: &Path, style: DescribeStyle) -> io::Result<String> {
 
   } else {
     // trying git rev-parse
-    get_lines("git", &["rev-parse", "HEAD"])
+    get_lines("git", &["rev-parse", "HEAD"], Some(wd))
       ?
       .into_iter()
       .next()
@@ -158,15 +218,18 @@ fn get_tag_or_hash(git_dir: &Path, style: DescribeStyle) -> io::Result<String> {
     Ok(cap.name("ref").unwrap().to_owned())
 
   } else if ref_.len() >= 7 {
-    Ok(format!("{}...", &ref_[7..]))
+    Ok(format!("{}...", &ref_[..7]))
 
   } else {
     Ok("unknown".to_owned())
   }
 }
 
-fn get_stash_count() -> Result<usize, io::Error> {
-  if communicate("git", &["rev-parse", "--verify", "--quiet", "refs/stash"]).is_err() {
+fn get_stash_count(wd: &Path) -> Result<usize, io::Error> {
+  let (_, _, status) = communicate("git",
+                                   &["rev-parse", "--verify", "--quiet", "refs/stash"],
+                                   Some(wd))?;
+  if status != 0 {
     return Ok(0);
   }
 
@@ -177,7 +240,8 @@ fn get_stash_count() -> Result<usize, io::Error> {
                                           "--first-parent",
                                           "-m",
                                           "refs/stash",
-                                          "--"])?;
+                                          "--"],
+                                        Some(wd))?;
   if stderr.contains("fatal") {
     return Err(io::Error::new(io::ErrorKind::Other, stderr.as_str()));
   }
@@ -186,11 +250,94 @@ fn get_stash_count() -> Result<usize, io::Error> {
 }
 
 
-pub fn current_status() -> io::Result<Option<Status>> {
+pub fn current_status(wd: &Path) -> io::Result<Option<Status>> {
+  // the in-process backend falls back on `git status` if it cannot handle the repository.
+  #[cfg(feature = "native-git")]
+  {
+    if let Ok(status) = ::vcs::git_native::current_status(wd) {
+      return Ok(status);
+    }
+  }
+  command_status(wd)
+}
+
+// the branch and the operation in progress, read from the files in `.git` without running
+// any command. the other fields are left empty.
+pub fn partial_status(wd: &Path) -> io::Result<Option<Status>> {
+  let git_dir = match get_git_dir(wd) {
+    Some(git_dir) => git_dir,
+    None => return Ok(None),
+  };
+
+  let head = read_content(git_dir.join("HEAD"))?;
+  let branch = if head.starts_with("ref: ") {
+    head[5..].trim_left_matches("refs/heads/").to_owned()
+  } else if head.len() >= 7 {
+    format!("({}...)", &head[..7])
+  } else {
+    "unknown".to_owned()
+  };
+
+  Ok(Some(Status {
+    branch: branch,
+    operation: get_operation(&git_dir).map(ToOwned::to_owned),
+    linked_worktree: is_linked_worktree(&git_dir),
+    sparse: is_sparse(&git_dir),
+    ..Status::default()
+  }))
+}
+
+// files which are updated whenever the status is likely to change.
+pub fn key_files(wd: &Path) -> Vec<PathBuf> {
+  let git_dir = match get_git_dir(wd) {
+    Some(git_dir) => git_dir,
+    None => return Vec::new(),
+  };
+  let common_dir = get_common_dir(&git_dir);
+  let mut files = vec![git_dir.join("index"),
+                       git_dir.join("HEAD"),
+                       git_dir.join("FETCH_HEAD"),
+                       common_dir.join("packed-refs"),
+                       common_dir.join("logs/refs/stash")];
+
+  // the branch and its upstream, which are moved by commits, `git fetch` and `git push`.
+  let head = read_content(git_dir.join("HEAD")).unwrap_or_default();
+  if head.starts_with("ref: refs/heads/") {
+    files.push(common_dir.join(&head[5..]));
+    if let Some(upstream) = get_upstream_ref(&common_dir, &head[16..]) {
+      files.push(common_dir.join(upstream));
+    }
+  }
+  files
+}
+
+// the remote-tracking branch of `branch`, e.g. `refs/remotes/origin/master`, assuming the
+// default refspec of the remote.
+fn get_upstream_ref(common_dir: &Path, branch: &str) -> Option<String> {
+  let config = read_content(common_dir.join("config")).ok()?;
+  let section = format!("branch \"{}\"", branch);
+  let remote = ini_value(&config, &section, "remote")?;
+  let merge = ini_value(&config, &section, "merge")?;
+  if remote == "." {
+    return Some(merge);
+  }
+  Some(format!("refs/remotes/{}/{}", remote, merge.trim_left_matches("refs/heads/")))
+}
+
+// collect the status by running `git status`.
+pub fn command_status(wd: &Path) -> io::Result<Option<Status>> {
   let mut status = Status::default();
 
+  // paths are relative to the top of the working tree, to be matched with submodules.
   let lines = get_lines("git",
-                        &["-c", "color.status=false", "status", "--short", "--branch"])?;
+                        &["-c",
+                          "color.status=false",
+                          "-c",
+                          "status.relativePaths=false",
+                          "status",
+                          "--short",
+                          "--branch"],
+                        Some(wd))?;
 
   // get branch information.
   if let Some(line_branch) = lines.iter().next() {
@@ -211,7 +358,7 @@ pub fn current_status() -> io::Result<Option<Status>> {
     return Ok(None);
   }
   if status.branch == "" {
-    status.branch = get_branch()?;
+    status.branch = get_branch(wd)?;
   }
 
 
@@ -219,7 +366,10 @@ pub fn current_status() -> io::Result<Option<Status>> {
   let mut index = DiffInfo::default();
   let mut working = DiffInfo::default();
 
-  let re = Regex::new(r"^(?P<index>[^#])(?P<working>.) (.*?)(?: -> (.*))?$").unwrap();
+  let re = Regex::new(r"^(?P<index>[^#])(?P<working>.) (?P<path>.*?)(?: -> (.*))?$").unwrap();
+  let submodules = get_local_or_parent_path(wd, ".git")
+    .and_then(|dot_git| dot_git.parent().map(get_submodule_paths))
+    .unwrap_or_default();
 
   for ref caps in (&lines[1..]).iter().filter_map(|ref line| re.captures(line)) {
     match caps.name("index") {
@@ -232,6 +382,19 @@ pub fn current_status() -> io::Result<Option<Status>> {
       _ => (),
     }
 
+    if submodules.iter().any(|path| Some(path.as_str()) == caps.name("path")) {
+      // `M` for new commits, `m` for modified content and `?` for untracked files.
+      match caps.name("working") {
+        Some("M") => {
+          working.modified += 1;
+          status.submodules += 1;
+        }
+        Some("m") | Some("?") => status.submodules += 1,
+        _ => (),
+      }
+      continue;
+    }
+
     match caps.name("working") {
       Some("A") => working.added += 1,
       Some("M") => working.modified += 1,
@@ -253,75 +416,281 @@ pub fn current_status() -> io::Result<Option<Status>> {
   }
 
   // collect stash count.
-  status.stash_count = get_stash_count()?;
+  status.stash_count = get_stash_count(wd)?;
+  if let Some(git_dir) = get_git_dir(wd) {
+    status.operation = get_operation(&git_dir).map(ToOwned::to_owned);
+    status.linked_worktree = is_linked_worktree(&git_dir);
+    status.sparse = is_sparse(&git_dir);
+  }
 
   Ok(Some(status))
 }
 
-impl Prompt for Status {
-  fn prompt(&self, fallback: bool) -> String {
-    let mut ret = String::new();
-
-    // show branch information
-    ret += &self.branch;
-    if self.upstream == "" {
-      // no upstream branch
-    } else if self.behind_by == 0 && self.ahead_by == 0 {
-      // aligned with remote
-      ret += if fallback { " =" } else { " ≡" };
-    } else if self.behind_by > 0 && self.ahead_by > 0 {
-      // both behind and ahead of remote
-      ret += if fallback { " AB" } else { " ↕" };
-    } else if self.ahead_by > 0 {
-      // ahead of remote
-      ret += if fallback { " A" } else { " ↑" };
-    } else if self.behind_by > 0 {
-      // behind remote
-      ret += if fallback { " B" } else { " ↓" };
-    } else {
-      // unknown state
-      ret += " ?";
-    }
-
-    if let Some(ref s) = self.index {
-      ret += " |I";
-      ret += &format!(" +{}", s.added);
-      ret += &format!(" ~{}", s.modified + s.renamed + s.copied);
-      ret += &format!(" -{}", s.deleted);
-      ret += &format!(" !{}", s.unmerged);
-    }
-
-    if let Some(ref s) = self.working {
-      ret += " |W";
-      ret += &format!(" +{}", s.added);
-      ret += &format!(" ~{}", s.modified + s.renamed + s.copied);
-      ret += &format!(" -{}", s.deleted);
-      ret += &format!(" !{}", s.unmerged);
-    }
+impl Fields for Status {
+  fn field(&self, name: &str, symbols: &Symbols) -> Option<String> {
+    let value = match name {
+      "branch" => self.branch.clone(),
+      "upstream" => self.upstream.clone(),
+      "ahead_by" => self.ahead_by.to_string(),
+      "behind_by" => self.behind_by.to_string(),
+      "untracked" => self.untracked.to_string(),
+      "stash_count" => self.stash_count.to_string(),
+      "operation" => self.operation.clone().unwrap_or_default(),
+      "submodules" => self.submodules.to_string(),
+      "linked_worktree" => self.linked_worktree.to_string(),
+      "sparse" => self.sparse.to_string(),
+      "upstream_symbol" if self.upstream == "" => String::new(),
+      "upstream_symbol" => symbols.upstream(self.ahead_by, self.behind_by).to_owned(),
+      "index" => self.index.is_some().to_string(),
+      "working" => self.working.is_some().to_string(),
+      _ => {
+        // fields of each column, e.g. `index.added`
+        let (diff, field) = match name.find('.') {
+          Some(i) => (&name[..i], &name[i + 1..]),
+          None => return None,
+        };
+        let diff = match diff {
+          "index" => &self.index,
+          "working" => &self.working,
+          _ => return None,
+        };
+        // the counts are zero while the column is clean.
+        let clean = DiffInfo::default();
+        return diff.as_ref().unwrap_or(&clean).field(field).map(|n| n.to_string());
+      }
+    };
+    Some(value)
+  }
 
-    if self.untracked > 0 {
-      ret += &format!(" |? {}", self.untracked);
+  fn style(&self, name: &str) -> Option<&'static str> {
+    match name {
+      "upstream" => {
+        match (self.ahead_by, self.behind_by) {
+          (0, 0) => Some("aligned"),
+          (_, 0) => Some("ahead"),
+          (0, _) => Some("behind"),
+          _ => Some("diverged"),
+        }
+      }
+      _ => None,
     }
+  }
+}
 
-    if self.stash_count > 0 {
-      ret += &format!(" |S {}", self.stash_count);
+impl DiffInfo {
+  fn field(&self, name: &str) -> Option<usize> {
+    match name {
+      "added" => Some(self.added),
+      "modified" => Some(self.modified),
+      "renamed" => Some(self.renamed),
+      "copied" => Some(self.copied),
+      "deleted" => Some(self.deleted),
+      "unmerged" => Some(self.unmerged),
+      "changed" => Some(self.modified + self.renamed + self.copied),
+      _ => None,
     }
-
-    ret
   }
 }
 
-pub fn clone(url: &Url, dest: &Path, depth: Option<i32>) -> Result<i32, io::Error> {
-  let depth = depth.map(|depth| format!("--depth={}", depth));
+pub fn clone(url: &Url,
+             dest: &Path,
+             opts: &CloneOptions,
+             out: &mut Output)
+             -> Result<i32, io::Error> {
+  let depth = opts.depth.map(|depth| format!("--depth={}", depth));
   let mut args = vec!["clone", url.as_str(), dest.to_str().unwrap()];
   if let Some(ref depth) = depth {
     args.push(&depth);
   }
+  if let Some(ref branch) = opts.branch {
+    args.extend(&["--branch", branch]);
+  }
+  if opts.bare {
+    args.push("--bare");
+  }
+  if opts.recursive {
+    args.push("--recursive");
+  }
 
-  wait_exec("git", args.as_slice(), None)
+  out.exec("git", args.as_slice(), None)
 }
 
-#[allow(dead_code)]
-pub fn update(dest: &Path) -> Result<i32, io::Error> {
-  wait_exec("git", &["pull", "--ff-only"], Some(dest))
+pub fn update(dest: &Path, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("git", &["pull", "--ff-only"], Some(dest))
+}
+
+pub fn update_to(dest: &Path, revision: &str, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("git", &["checkout", "--quiet", revision], Some(dest))
+}
+
+pub fn revision(dest: &Path) -> Result<Option<String>, io::Error> {
+  get_lines("git", &["-C", dest.to_str().unwrap(), "rev-parse", "HEAD"], None)
+    .map(|lines| lines.into_iter().next())
+}
+
+// the number of commits reachable from local refs (and a detached HEAD) but from no
+// remote-tracking branch. stashes are excluded since they are counted separately.
+pub fn unpushed_commits(dest: &Path) -> Result<Option<usize>, io::Error> {
+  // branches of a bare clone are copied from the remote, without remote-tracking branches to
+  // tell which commits are pushed.
+  if !dest.join(".git").exists() &&
+     get_lines("git",
+               &["-C", dest.to_str().unwrap(), "for-each-ref", "--count=1", "refs/remotes"],
+               None)?
+    .is_empty() {
+    return Ok(None);
+  }
+  let (stdout, _, status) = communicate("git",
+                                        &["-C",
+                                          dest.to_str().unwrap(),
+                                          "rev-list",
+                                          "--count",
+                                          "--exclude=refs/stash",
+                                          "--all",
+                                          "--not",
+                                          "--remotes"],
+                                        None)?;
+  if status != 0 {
+    return Ok(None);
+  }
+  Ok(stdout.trim().parse().ok())
+}
+
+// the URL of `origin`, read from the configuration of the repository.
+pub fn remote_url(dest: &Path) -> Result<Option<String>, io::Error> {
+  // a working copy or a bare repository
+  let config = if dest.join(".git").is_dir() {
+    dest.join(".git/config")
+  } else {
+    dest.join("config")
+  };
+  if config.is_file() {
+    return read_content(config).map(|content| ini_value(&content, "remote \"origin\"", "url"));
+  }
+
+  // e.g. `.git` is a file which points to the actual directory
+  get_lines("git",
+            &["-C", dest.to_str().unwrap(), "config", "--get", "remote.origin.url"],
+            None)
+    .map(|lines| lines.into_iter().next())
+}
+
+
+#[cfg(test)]
+mod test_current_status {
+  use std::env;
+  use std::fs::{self, File};
+  use std::io::Write;
+  use std::path::Path;
+  use super::{current_status, get_git_dir, partial_status, revision};
+  use testing::{self, git, TempDir};
+
+  fn write(path: &Path, content: &str) {
+    File::create(path).unwrap().write_all(content.as_bytes()).unwrap();
+  }
+
+  #[test]
+  fn outside_of_current_dir() {
+    if !testing::available("git") {
+      return;
+    }
+    let dir = TempDir::new("git-status");
+    assert!(git(&dir, &["init", "--quiet"]));
+    assert!(git(&dir, &["symbolic-ref", "HEAD", "refs/heads/main"]));
+    write(&dir.join("a.txt"), "a");
+    assert!(git(&dir, &["add", "a.txt"]));
+    assert!(git(&dir, &["commit", "--quiet", "-m", "first"]));
+
+    write(&dir.join("a.txt"), "b");
+    assert!(git(&dir, &["stash", "--quiet"]));
+    write(&dir.join("a.txt"), "c");
+    write(&dir.join("b.txt"), "b");
+    assert!(git(&dir, &["add", "b.txt"]));
+    write(&dir.join("c.txt"), "c");
+
+    assert!(env::current_dir().unwrap() != dir.path());
+    let status = current_status(&dir).unwrap().unwrap();
+    assert_eq!(status.branch, "main");
+    assert_eq!(status.index.as_ref().map(|d| d.added), Some(1));
+    assert_eq!(status.working.as_ref().map(|d| d.modified), Some(1));
+    assert_eq!(status.untracked, 1);
+    assert_eq!(status.stash_count, 1);
+    assert_eq!(status.operation, None);
+
+    // the abbreviated hash of the detached HEAD, the same as `partial_status`.
+    assert!(git(&dir, &["checkout", "--quiet", "--detach"]));
+    let hash = revision(&dir).unwrap().unwrap();
+    assert_eq!(current_status(&dir).unwrap().unwrap().branch, format!("({}...)", &hash[..7]));
+    assert_eq!(partial_status(&dir).unwrap().unwrap().branch, format!("({}...)", &hash[..7]));
+  }
+
+  #[test]
+  fn submodules_and_worktrees() {
+    if !testing::available("git") {
+      return;
+    }
+    let dir = TempDir::new("git-submodules");
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::create_dir_all(dir.join("main")).unwrap();
+    assert!(git(&dir.join("lib"), &["init", "--quiet"]));
+    write(&dir.join("lib/a.txt"), "a");
+    assert!(git(&dir.join("lib"), &["add", "a.txt"]));
+    assert!(git(&dir.join("lib"), &["commit", "--quiet", "-m", "first"]));
+
+    let main = dir.join("main");
+    assert!(git(&main, &["init", "--quiet"]));
+    assert!(git(&main, &["symbolic-ref", "HEAD", "refs/heads/main"]));
+    for sub in &["sub1", "sub2", "sub3"] {
+      assert!(git(&main,
+                  &["-c", "protocol.file.allow=always", "submodule", "add", "--quiet", "../lib",
+                    sub]));
+    }
+    assert!(git(&main, &["commit", "--quiet", "-m", "submodules"]));
+
+    // modified content, untracked files and a new commit
+    write(&main.join("sub1/a.txt"), "b");
+    write(&main.join("sub2/b.txt"), "b");
+    write(&main.join("sub3/a.txt"), "c");
+    assert!(git(&main.join("sub3"), &["commit", "--quiet", "-a", "-m", "second"]));
+
+    let status = current_status(&main).unwrap().unwrap();
+    assert_eq!(status.submodules, 3);
+    assert_eq!(status.untracked, 0);
+    assert_eq!(status.working.as_ref().map(|d| d.modified), Some(1));
+    assert!(!status.linked_worktree);
+    assert!(!status.sparse);
+
+    // `.git` of submodules points to `.git/modules` of the superproject.
+    let sub = current_status(&main.join("sub1")).unwrap().unwrap();
+    assert_eq!(sub.working.as_ref().map(|d| d.modified), Some(1));
+    assert_eq!(get_git_dir(&main.join("sub1")), Some(main.join(".git/modules/sub1")));
+
+    // sparse checkout in a linked worktree is configured in its own `config.worktree`.
+    assert!(git(&main, &["worktree", "add", "--quiet", "-b", "topic", "../wt"]));
+    assert!(git(&dir.join("wt"), &["sparse-checkout", "init"]));
+    let status = current_status(&dir.join("wt")).unwrap().unwrap();
+    assert_eq!(status.branch, "topic");
+    assert!(status.linked_worktree);
+    assert!(status.sparse);
+    assert!(!current_status(&main).unwrap().unwrap().sparse);
+    let status = partial_status(&dir.join("wt")).unwrap().unwrap();
+    assert_eq!(status.branch, "topic");
+    assert!(status.linked_worktree);
+  }
+
+  #[test]
+  fn partial() {
+    let dir = TempDir::new("git-partial");
+    fs::create_dir_all(dir.join(".git/rebase-merge")).unwrap();
+    fs::create_dir_all(dir.join("sub")).unwrap();
+
+    write(&dir.join(".git/HEAD"), "ref: refs/heads/topic/x\n");
+    let status = partial_status(&dir.join("sub")).unwrap().unwrap();
+    assert_eq!(status.branch, "topic/x");
+    assert_eq!(status.operation, Some("REBASE-m".to_owned()));
+    assert!(status.index.is_none());
+
+    write(&dir.join(".git/HEAD"), "0123456789abcdef0123456789abcdef01234567\n");
+    assert_eq!(partial_status(&dir).unwrap().unwrap().branch, "(0123456...)");
+  }
 }
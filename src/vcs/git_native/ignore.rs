@@ -0,0 +1,295 @@
+// Matching paths against the patterns of `.gitignore`.
+
+use std::path::Path;
+use util::read_content;
+
+struct Pattern {
+  glob: Vec<u8>,
+  negative: bool,
+  dir_only: bool,
+  // matched against the whole path instead of the basename
+  anchored: bool,
+}
+
+impl Pattern {
+  fn parse(line: &str) -> Option<Pattern> {
+    let mut line = line.trim_end_matches(|c| c == ' ' || c == '\r');
+    if line.is_empty() || line.starts_with('#') {
+      return None;
+    }
+
+    let negative = line.starts_with('!');
+    if negative || line.starts_with('\\') {
+      line = &line[1..];
+    }
+    let dir_only = line.ends_with('/');
+    if dir_only {
+      line = &line[..line.len() - 1];
+    }
+    if line.is_empty() {
+      return None;
+    }
+    let anchored = line.contains('/');
+    let glob = line.trim_start_matches('/');
+
+    Some(Pattern {
+      glob: glob.as_bytes().to_vec(),
+      negative: negative,
+      dir_only: dir_only,
+      anchored: anchored,
+    })
+  }
+
+  fn matches(&self, path: &str, is_dir: bool) -> bool {
+    if self.dir_only && !is_dir {
+      return false;
+    }
+    if self.anchored {
+      wildmatch(&self.glob, path.as_bytes())
+    } else {
+      let name = path.rsplit('/').next().unwrap_or(path);
+      wildmatch(&self.glob, name.as_bytes())
+    }
+  }
+}
+
+// A list of patterns read from a file, relative to the directory `base`.
+struct PatternList {
+  base: String,
+  patterns: Vec<Pattern>,
+}
+
+impl PatternList {
+  fn parse(base: &str, content: &str) -> PatternList {
+    PatternList {
+      base: base.to_owned(),
+      patterns: content.lines().filter_map(Pattern::parse).collect(),
+    }
+  }
+
+  // `Some(true)` if ignored, `Some(false)` if explicitly included.
+  fn matches(&self, path: &str, is_dir: bool) -> Option<bool> {
+    let rel = if self.base.is_empty() {
+      path
+    } else if path.starts_with(&self.base) && path[self.base.len()..].starts_with('/') {
+      &path[self.base.len() + 1..]
+    } else {
+      return None;
+    };
+    self.patterns
+      .iter()
+      .rev()
+      .find(|p| p.matches(rel, is_dir))
+      .map(|p| !p.negative)
+  }
+}
+
+
+// The stack of ignore rules while walking the working tree.
+pub struct Ignore {
+  // `.git/info/exclude` and `core.excludesFile`, in the order of precedence
+  global: Vec<PatternList>,
+  // `.gitignore` of the directories from the root to the current one
+  stack: Vec<PatternList>,
+}
+
+impl Ignore {
+  pub fn new(git_dir: &Path, excludes_file: Option<&Path>) -> Ignore {
+    let mut global = Vec::new();
+    if let Ok(content) = read_content(git_dir.join("info/exclude")) {
+      global.push(PatternList::parse("", &content));
+    }
+    if let Some(content) = excludes_file.and_then(|path| read_content(path).ok()) {
+      global.push(PatternList::parse("", &content));
+    }
+    Ignore {
+      global: global,
+      stack: Vec::new(),
+    }
+  }
+
+  // enter the directory `rel` (relative to the top of working tree).
+  pub fn push(&mut self, worktree: &Path, rel: &str) {
+    let content = read_content(worktree.join(rel).join(".gitignore")).unwrap_or_default();
+    self.stack.push(PatternList::parse(rel, &content));
+  }
+
+  pub fn pop(&mut self) {
+    self.stack.pop();
+  }
+
+  // the closest `.gitignore` takes precedence, and the last matching pattern wins in a file.
+  pub fn is_ignored(&self, path: &str, is_dir: bool) -> bool {
+    self.stack
+      .iter()
+      .rev()
+      .chain(self.global.iter())
+      .filter_map(|list| list.matches(path, is_dir))
+      .next()
+      .unwrap_or(false)
+  }
+}
+
+
+// match a glob in the manner of `wildmatch()` with `WM_PATHNAME`:
+// `*` and `?` never match a slash, and `**` between slashes matches any directories.
+fn wildmatch(pattern: &[u8], text: &[u8]) -> bool {
+  let mut p = 0;
+  let mut t = 0;
+  while p < pattern.len() {
+    match pattern[p] {
+      b'*' => {
+        let double = pattern.get(p + 1) == Some(&b'*');
+        if double && (p == 0 || pattern[p - 1] == b'/') &&
+           (p + 2 == pattern.len() || pattern[p + 2] == b'/') {
+          if p + 2 == pattern.len() {
+            // trailing `/**` matches everything inside
+            return true;
+          }
+          // `**/` matches zero or more directories
+          let rest = &pattern[p + 3..];
+          let mut start = t;
+          loop {
+            if wildmatch(rest, &text[start..]) {
+              return true;
+            }
+            match text[start..].iter().position(|&b| b == b'/') {
+              Some(slash) => start += slash + 1,
+              None => return false,
+            }
+          }
+        }
+
+        let mut rest = p + 1;
+        while pattern.get(rest) == Some(&b'*') {
+          rest += 1;
+        }
+        let mut end = t;
+        loop {
+          if wildmatch(&pattern[rest..], &text[end..]) {
+            return true;
+          }
+          if end == text.len() || text[end] == b'/' {
+            return false;
+          }
+          end += 1;
+        }
+      }
+      b'?' => {
+        if t == text.len() || text[t] == b'/' {
+          return false;
+        }
+        p += 1;
+        t += 1;
+      }
+      b'[' => {
+        if t == text.len() || text[t] == b'/' {
+          return false;
+        }
+        match match_class(&pattern[p..], text[t]) {
+          Some((true, len)) => {
+            p += len;
+            t += 1;
+          }
+          Some((false, _)) => return false,
+          // an unclosed bracket matches literally
+          None => {
+            if text[t] != b'[' {
+              return false;
+            }
+            p += 1;
+            t += 1;
+          }
+        }
+      }
+      c => {
+        let c = if c == b'\\' && p + 1 < pattern.len() {
+          p += 1;
+          pattern[p]
+        } else {
+          c
+        };
+        if t == text.len() || text[t] != c {
+          return false;
+        }
+        p += 1;
+        t += 1;
+      }
+    }
+  }
+  t == text.len()
+}
+
+// match a character class `[...]` at the head of the pattern.
+// returns whether it matched and the length of the class.
+fn match_class(pattern: &[u8], c: u8) -> Option<(bool, usize)> {
+  let mut i = 1;
+  let negated = pattern.get(i) == Some(&b'!') || pattern.get(i) == Some(&b'^');
+  if negated {
+    i += 1;
+  }
+
+  let mut matched = false;
+  let mut first = true;
+  loop {
+    let lo = *pattern.get(i)?;
+    if lo == b']' && !first {
+      break;
+    }
+    first = false;
+    let lo = if lo == b'\\' {
+      i += 1;
+      *pattern.get(i)?
+    } else {
+      lo
+    };
+    if pattern.get(i + 1) == Some(&b'-') && pattern.get(i + 2).map(|&b| b != b']') == Some(true) {
+      let hi = pattern[i + 2];
+      matched |= lo <= c && c <= hi;
+      i += 3;
+    } else {
+      matched |= lo == c;
+      i += 1;
+    }
+  }
+
+  Some((matched != negated, i + 1))
+}
+
+
+#[cfg(test)]
+mod test_ignore {
+  use super::{PatternList, wildmatch};
+
+  #[test]
+  fn globs() {
+    assert!(wildmatch(b"*.o", b"main.o"));
+    assert!(!wildmatch(b"*.o", b"src/main.o"));
+    assert!(wildmatch(b"foo?[0-9]", b"foo-1"));
+    assert!(!wildmatch(b"foo[!0-9]", b"foo1"));
+    assert!(wildmatch(b"**/target", b"a/b/target"));
+    assert!(wildmatch(b"**/target", b"target"));
+    assert!(wildmatch(b"doc/**", b"doc/a/b.md"));
+    assert!(wildmatch(b"a/**/b", b"a/b"));
+    assert!(wildmatch(b"a/**/b", b"a/x/y/b"));
+    assert!(!wildmatch(b"a/*/b", b"a/x/y/b"));
+  }
+
+  #[test]
+  fn patterns() {
+    let list = PatternList::parse("sub",
+                                  "# comment\n\
+                                   *.log\n\
+                                   !keep.log\n\
+                                   /build/\n\
+                                   docs/*.html\n");
+    assert_eq!(list.matches("sub/a.log", false), Some(true));
+    assert_eq!(list.matches("sub/x/a.log", false), Some(true));
+    assert_eq!(list.matches("sub/keep.log", false), Some(false));
+    assert_eq!(list.matches("sub/build", true), Some(true));
+    assert_eq!(list.matches("sub/build", false), None);
+    assert_eq!(list.matches("sub/x/build", true), None);
+    assert_eq!(list.matches("sub/docs/index.html", false), Some(true));
+    assert_eq!(list.matches("other/a.log", false), None);
+  }
+}
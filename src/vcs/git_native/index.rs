@@ -0,0 +1,149 @@
+// Parsing `.git/index` (the versions 2 and 3 of the format).
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use super::{Oid, invalid};
+
+// flags of an entry
+const ASSUME_VALID: u16 = 0x8000;
+const EXTENDED: u16 = 0x4000;
+// extended flags
+const SKIP_WORKTREE: u16 = 0x4000;
+const INTENT_TO_ADD: u16 = 0x2000;
+
+pub const MODE_GITLINK: u32 = 0o160000;
+
+pub struct Entry {
+  pub mtime: (u32, u32),
+  pub mode: u32,
+  pub size: u32,
+  pub id: Oid,
+  pub stage: u8,
+  pub assume_valid: bool,
+  pub skip_worktree: bool,
+  pub intent_to_add: bool,
+  pub path: String,
+}
+
+pub struct Index {
+  pub entries: Vec<Entry>,
+  // the tree of the cache-tree extension, if the whole index is covered by it
+  pub tree: Option<Oid>,
+  // the modified time of the index itself, to detect racily clean entries
+  pub mtime: (u32, u32),
+}
+
+impl Index {
+  pub fn read(path: &Path) -> io::Result<Index> {
+    let mut file = match File::open(path) {
+      Ok(file) => file,
+      Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+        // no files have been added yet.
+        return Ok(Index {
+          entries: Vec::new(),
+          tree: None,
+          mtime: (0, 0),
+        });
+      }
+      Err(err) => return Err(err),
+    };
+    let mtime = super::mtime_of(&file.metadata()?);
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+
+    if buf.len() < 12 + 20 || &buf[..4] != b"DIRC" {
+      return Err(invalid("invalid index"));
+    }
+    let version = read_u32(&buf, 4);
+    if version != 2 && version != 3 {
+      // the version 4 compresses paths, which is not supported.
+      return Err(invalid("unsupported version of index"));
+    }
+    let count = read_u32(&buf, 8) as usize;
+    let end = buf.len() - 20;
+
+    let mut entries = Vec::with_capacity(count);
+    let mut pos = 12;
+    for _ in 0..count {
+      if pos + 62 > end {
+        return Err(invalid("truncated index"));
+      }
+      let flags = read_u16(&buf, pos + 60);
+      let mut name = pos + 62;
+      let mut extended = 0;
+      if flags & EXTENDED != 0 {
+        extended = read_u16(&buf, name);
+        name += 2;
+      }
+      let len = buf[name..end].iter().position(|&b| b == 0).ok_or(invalid("truncated index"))?;
+      let mode = read_u32(&buf, pos + 24);
+      if mode & 0o170000 == 0o040000 {
+        // a sparse directory
+        return Err(invalid("sparse index is not supported"));
+      }
+
+      entries.push(Entry {
+        mtime: (read_u32(&buf, pos + 8), read_u32(&buf, pos + 12)),
+        mode: mode,
+        size: read_u32(&buf, pos + 36),
+        id: Oid::from_bytes(&buf[pos + 40..pos + 60]),
+        stage: ((flags >> 12) & 3) as u8,
+        assume_valid: flags & ASSUME_VALID != 0,
+        skip_worktree: extended & SKIP_WORKTREE != 0,
+        intent_to_add: extended & INTENT_TO_ADD != 0,
+        path: String::from_utf8_lossy(&buf[name..name + len]).into_owned(),
+      });
+
+      // entries are padded with 1-8 NULs to a multiple of eight bytes.
+      let entry_len = name - pos + len;
+      pos += (entry_len + 8) & !7;
+    }
+
+    // extensions
+    let mut tree = None;
+    while pos + 8 <= end {
+      let signature = &buf[pos..pos + 4];
+      let size = read_u32(&buf, pos + 4) as usize;
+      let data = buf.get(pos + 8..pos + 8 + size).ok_or(invalid("truncated index"))?;
+      match signature {
+        b"TREE" => tree = root_tree(data),
+        b"link" => return Err(invalid("split index is not supported")),
+        _ if signature[0] >= b'A' && signature[0] <= b'Z' => (),
+        _ => return Err(invalid("unknown mandatory extension of index")),
+      }
+      pos += 8 + size;
+    }
+
+    Ok(Index {
+      entries: entries,
+      tree: tree,
+      mtime: mtime,
+    })
+  }
+}
+
+// the root of the cache-tree: `<path>\0<entries> <subtrees>\n<id>`.
+// the number of entries is `-1` if it has been invalidated.
+fn root_tree(data: &[u8]) -> Option<Oid> {
+  let nul = data.iter().position(|&b| b == 0)?;
+  if nul != 0 {
+    return None;
+  }
+  let newline = data.iter().position(|&b| b == b'\n')?;
+  let counts = String::from_utf8_lossy(&data[1..newline]).into_owned();
+  let entries: i64 = counts.split(' ').next()?.parse().ok()?;
+  if entries < 0 {
+    return None;
+  }
+  data.get(newline + 1..newline + 21).map(Oid::from_bytes)
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> u32 {
+  (buf[pos] as u32) << 24 | (buf[pos + 1] as u32) << 16 | (buf[pos + 2] as u32) << 8 |
+  buf[pos + 3] as u32
+}
+
+fn read_u16(buf: &[u8], pos: usize) -> u16 {
+  (buf[pos] as u16) << 8 | buf[pos + 1] as u16
+}
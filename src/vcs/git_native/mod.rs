@@ -0,0 +1,937 @@
+// An in-process backend of `git::current_status()`, enabled by the feature `native-git`.
+//
+// It reads `.git/HEAD`, refs, `packed-refs`, the index and the reflog of stashes directly
+// instead of spawning `git status`. Repositories which it cannot handle in the same manner
+// as Git (e.g. linked worktrees, submodules, sparse or split indexes, and content filters
+// configured by `.gitattributes`) are reported as errors, and the caller falls back on the
+// subprocess.
+
+mod ignore;
+mod index;
+mod object;
+
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::env;
+use std::fs::{self, Metadata};
+use std::io;
+use std::path::{Path, PathBuf};
+use util::{ini_bool, ini_value, read_content};
+use vcs::git::{DiffInfo, Status, get_operation, is_sparse};
+use self::ignore::Ignore;
+use self::index::{Index, MODE_GITLINK};
+use self::object::{Commit, Kind, ObjectDb};
+
+// the environment variables which change the layout of the repository
+const GIT_ENV: &'static [&'static str] = &["GIT_DIR",
+                                           "GIT_WORK_TREE",
+                                           "GIT_INDEX_FILE",
+                                           "GIT_OBJECT_DIRECTORY",
+                                           "GIT_COMMON_DIR"];
+
+// the environment variables which add or replace configuration files
+const GIT_CONFIG_ENV: &'static [&'static str] = &["GIT_CONFIG",
+                                                  "GIT_CONFIG_GLOBAL",
+                                                  "GIT_CONFIG_SYSTEM",
+                                                  "GIT_CONFIG_COUNT",
+                                                  "GIT_CONFIG_PARAMETERS"];
+
+// the limit of commits to visit while counting ahead/behind
+const MAX_COMMITS: usize = 100000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Oid([u8; 20]);
+
+impl Oid {
+  fn from_bytes(bytes: &[u8]) -> Oid {
+    let mut id = [0u8; 20];
+    id.copy_from_slice(&bytes[..20]);
+    Oid(id)
+  }
+
+  fn from_hex(hex: &[u8]) -> io::Result<Oid> {
+    fn digit(c: u8) -> io::Result<u8> {
+      match c {
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        _ => Err(invalid("invalid object name")),
+      }
+    }
+    if hex.len() < 40 {
+      return Err(invalid("invalid object name"));
+    }
+    let mut id = [0u8; 20];
+    for (i, byte) in id.iter_mut().enumerate() {
+      *byte = digit(hex[i * 2])? << 4 | digit(hex[i * 2 + 1])?;
+    }
+    Ok(Oid(id))
+  }
+
+  fn to_hex(&self) -> String {
+    to_hex(&self.0)
+  }
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn invalid(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+// the configuration files read by Git on Unix: the repository, per-user and system-wide ones, in
+// the order of precedence. the system-wide one depends on the installation, so both of the
+// common locations are read.
+#[cfg(unix)]
+fn config_files(git_dir: &Path,
+                home: Option<&PathBuf>,
+                xdg: Option<&PathBuf>)
+                -> io::Result<Vec<PathBuf>> {
+  if GIT_CONFIG_ENV.iter().any(|name| env::var_os(name).is_some()) {
+    return Err(unsupported("the configuration is overridden by the environment"));
+  }
+
+  let mut files = vec![git_dir.join("config")];
+  if let Some(home) = home {
+    files.push(home.join(".gitconfig"));
+  }
+  if let Some(xdg) = xdg {
+    files.push(xdg.join("git/config"));
+  }
+  if env::var_os("GIT_CONFIG_NOSYSTEM").is_none() {
+    files.push(PathBuf::from("/etc/gitconfig"));
+    files.push(PathBuf::from("/usr/local/etc/gitconfig"));
+  }
+  Ok(files)
+}
+
+// the locations depend on the installation on Windows.
+#[cfg(not(unix))]
+fn config_files(_: &Path, _: Option<&PathBuf>, _: Option<&PathBuf>) -> io::Result<Vec<PathBuf>> {
+  Err(unsupported("configuration files on Windows"))
+}
+
+fn unsupported(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::Other, msg)
+}
+
+#[cfg(unix)]
+fn mtime_of(meta: &Metadata) -> (u32, u32) {
+  use std::os::unix::fs::MetadataExt;
+  (meta.mtime() as u32, meta.mtime_nsec() as u32)
+}
+
+#[cfg(not(unix))]
+fn mtime_of(meta: &Metadata) -> (u32, u32) {
+  meta.modified()
+    .ok()
+    .and_then(|t| t.duration_since(::std::time::UNIX_EPOCH).ok())
+    .map(|d| (d.as_secs() as u32, d.subsec_nanos()))
+    .unwrap_or((0, 0))
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &Metadata) -> bool {
+  use std::os::unix::fs::PermissionsExt;
+  meta.permissions().mode() & 0o100 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_: &Metadata) -> bool {
+  false
+}
+
+
+struct Repo {
+  git_dir: PathBuf,
+  worktree: PathBuf,
+  // the repository, per-user and system-wide configurations, in the order of precedence
+  config: Vec<String>,
+  home: Option<PathBuf>,
+  xdg: Option<PathBuf>,
+  packed_refs: BTreeMap<String, (Oid, Option<Oid>)>,
+  objects: ObjectDb,
+}
+
+impl Repo {
+  fn open(wd: &Path) -> io::Result<Repo> {
+    let home = env::var_os("HOME").map(PathBuf::from);
+    let xdg = env::var_os("XDG_CONFIG_HOME")
+      .map(PathBuf::from)
+      .or(home.as_ref().map(|h| h.join(".config")));
+    Repo::open_with(wd, home, xdg)
+  }
+
+  // `home` and `xdg` (`$XDG_CONFIG_HOME`) locate the per-user configuration.
+  fn open_with(wd: &Path, home: Option<PathBuf>, xdg: Option<PathBuf>) -> io::Result<Repo> {
+    if GIT_ENV.iter().any(|name| env::var_os(name).is_some()) {
+      return Err(unsupported("the layout of repository is overridden by the environment"));
+    }
+
+    let mut worktree = wd.to_path_buf();
+    while !worktree.join(".git").exists() {
+      if !worktree.pop() {
+        return Err(unsupported("not a repository"));
+      }
+    }
+    let git_dir = worktree.join(".git");
+    if !git_dir.is_dir() {
+      return Err(unsupported("the Git directory is located outside the working tree"));
+    }
+    if git_dir.join("commondir").exists() {
+      return Err(unsupported("linked worktree"));
+    }
+
+    // a missing file is read as empty, so that the first one is always of the repository.
+    let config: Vec<String> = config_files(&git_dir, home.as_ref(), xdg.as_ref())?
+      .into_iter()
+      .map(|path| read_content(path).unwrap_or_default())
+      .collect();
+    if config.iter().any(|c| c.contains("[include")) {
+      return Err(unsupported("included configuration"));
+    }
+    if ini_bool(&config[0], "core", "bare").unwrap_or(false) {
+      return Err(unsupported("bare repository"));
+    }
+
+    let packed_refs = read_packed_refs(&git_dir)?;
+    let objects = ObjectDb::open(&git_dir.join("objects"))?;
+    let repo = Repo {
+      git_dir: git_dir,
+      worktree: worktree,
+      config: config,
+      home: home,
+      xdg: xdg,
+      packed_refs: packed_refs,
+      objects: objects,
+    };
+
+    if repo.config("core", "autocrlf").map(|v| v != "false").unwrap_or(false) ||
+       repo.config("status", "showUntrackedFiles").is_some() ||
+       repo.git_dir.join("info/attributes").exists() {
+      return Err(unsupported("unsupported configuration"));
+    }
+    Ok(repo)
+  }
+
+  // the value in the configuration of the highest precedence.
+  fn config(&self, section: &str, key: &str) -> Option<String> {
+    self.config
+      .iter()
+      .filter_map(|config| ini_value(config, section, key))
+      .next()
+      .map(|v| v.trim_matches('"').to_owned())
+  }
+
+  fn config_bool(&self, section: &str, key: &str) -> Option<bool> {
+    self.config.iter().filter_map(|config| ini_bool(config, section, key)).next()
+  }
+
+  // resolve a reference to an object, following symbolic references.
+  fn resolve(&self, name: &str) -> io::Result<Option<Oid>> {
+    let mut name = name.to_owned();
+    for _ in 0..5 {
+      match read_content(self.git_dir.join(&name)) {
+        Ok(ref content) if content.starts_with("ref: ") => name = content[5..].to_owned(),
+        Ok(content) => return Oid::from_hex(content.as_bytes()).map(Some),
+        Err(_) => return Ok(self.packed_refs.get(&name).map(|&(id, _)| id)),
+      }
+    }
+    Err(invalid("too deep symbolic references"))
+  }
+
+  fn commit(&self, id: &Oid) -> io::Result<Commit> {
+    let obj = self.objects.read(id)?;
+    if obj.kind != Kind::Commit {
+      return Err(invalid("not a commit"));
+    }
+    Commit::parse(&obj.data)
+  }
+
+  // the objects pointed by tags, with their names: `git tag --points-at`
+  fn tags(&self) -> io::Result<BTreeMap<String, Oid>> {
+    let mut tags = BTreeMap::new();
+    for (name, &(id, peeled)) in self.packed_refs.range("refs/tags/".to_owned()..) {
+      if !name.starts_with("refs/tags/") {
+        break;
+      }
+      tags.insert(name[10..].to_owned(), peeled.unwrap_or(id));
+    }
+
+    let mut loose = Vec::new();
+    collect_loose_refs(&self.git_dir.join("refs/tags"), "", &mut loose)?;
+    for (name, id) in loose {
+      let obj = self.objects.read(&id)?;
+      let target = if obj.kind == Kind::Tag {
+        object::tag_target(&obj.data)?
+      } else {
+        id
+      };
+      tags.insert(name, target);
+    }
+    Ok(tags)
+  }
+
+  // the tracking reference of the branch, and its abbreviated name.
+  fn upstream(&self, branch: &str) -> Option<(String, String)> {
+    let section = format!("branch \"{}\"", branch);
+    let remote = self.config(&section, "remote")?;
+    let merge = self.config(&section, "merge")?;
+    if remote == "." {
+      return Some((merge.clone(), shorten_ref(&merge)));
+    }
+
+    // map the remote branch by the refspec, e.g. `+refs/heads/*:refs/remotes/origin/*`
+    let refspec = self.config(&format!("remote \"{}\"", remote), "fetch")?;
+    let mut refspec = refspec.trim_start_matches('+').splitn(2, ':');
+    let (src, dst) = (refspec.next()?, refspec.next()?);
+    let tracking = match (src.find('*'), dst.find('*')) {
+      (Some(s), Some(d)) => {
+        if !merge.starts_with(&src[..s]) || !merge.ends_with(&src[s + 1..]) {
+          return None;
+        }
+        let matched = &merge[s..merge.len() - (src.len() - s - 1)];
+        format!("{}{}{}", &dst[..d], matched, &dst[d + 1..])
+      }
+      (None, None) if src == merge => dst.to_owned(),
+      _ => return None,
+    };
+    let short = shorten_ref(&tracking);
+    Some((tracking, short))
+  }
+
+  fn shallow_commits(&self) -> HashSet<Oid> {
+    read_content(self.git_dir.join("shallow"))
+      .unwrap_or_default()
+      .lines()
+      .filter_map(|line| Oid::from_hex(line.as_bytes()).ok())
+      .collect()
+  }
+
+  // the number of commits reachable only from `left` and only from `right`.
+  fn count_divergence(&self, left: Oid, right: Oid) -> io::Result<(usize, usize)> {
+    const LEFT: u8 = 1;
+    const RIGHT: u8 = 2;
+    const BOTH: u8 = LEFT | RIGHT;
+
+    if left == right {
+      return Ok((0, 0));
+    }
+
+    let shallow = self.shallow_commits();
+    let mut commits = HashMap::new();
+    let mut flags = HashMap::new();
+    let mut queue = BinaryHeap::new();
+    for &(id, flag) in &[(left, LEFT), (right, RIGHT)] {
+      let commit = self.commit(&id)?;
+      queue.push((commit.time, id));
+      commits.insert(id, commit);
+      flags.insert(id, flag);
+    }
+
+    // visit commits in the order of committer date, until only common ancestors remain.
+    while queue.iter().any(|&(_, ref id)| flags[id] != BOTH) {
+      let (_, id) = queue.pop().unwrap();
+      if commits.len() > MAX_COMMITS {
+        return Err(unsupported("too many commits to count"));
+      }
+      let flag = flags[&id];
+      if shallow.contains(&id) {
+        continue;
+      }
+      let parents = commits[&id].parents.clone();
+      for parent in parents {
+        let current = flags.get(&parent).cloned().unwrap_or(0);
+        if current | flag == current {
+          continue;
+        }
+        flags.insert(parent, current | flag);
+        if !commits.contains_key(&parent) {
+          let commit = self.commit(&parent)?;
+          commits.insert(parent, commit);
+        }
+        queue.push((commits[&parent].time, parent));
+      }
+    }
+
+    Ok((flags.values().filter(|&&f| f == LEFT).count(),
+        flags.values().filter(|&&f| f == RIGHT).count()))
+  }
+
+  // the blobs in the tree, with their modes.
+  fn flatten_tree(&self,
+                  id: &Oid,
+                  prefix: &str,
+                  files: &mut BTreeMap<String, (u32, Oid)>)
+                  -> io::Result<()> {
+    let obj = self.objects.read(id)?;
+    if obj.kind != Kind::Tree {
+      return Err(invalid("not a tree"));
+    }
+    for (mode, name, id) in object::tree_entries(&obj.data)? {
+      let path = format!("{}{}", prefix, String::from_utf8_lossy(&name));
+      if mode == 0o040000 {
+        self.flatten_tree(&id, &format!("{}/", path), files)?;
+      } else {
+        files.insert(path, (mode, id));
+      }
+    }
+    Ok(())
+  }
+
+  // `core.excludesFile`, or its default location.
+  fn excludes_file(&self) -> Option<PathBuf> {
+    match self.config("core", "excludesFile") {
+      Some(path) => {
+        if path.starts_with("~/") {
+          self.home.as_ref().map(|h| h.join(&path[2..]))
+        } else {
+          Some(PathBuf::from(path))
+        }
+      }
+      None => self.xdg.as_ref().map(|x| x.join("git/ignore")),
+    }
+  }
+}
+
+fn read_packed_refs(git_dir: &Path) -> io::Result<BTreeMap<String, (Oid, Option<Oid>)>> {
+  let mut refs = BTreeMap::new();
+  let content = match read_content(git_dir.join("packed-refs")) {
+    Ok(content) => content,
+    Err(_) => return Ok(refs),
+  };
+
+  let mut last: Option<String> = None;
+  for line in content.lines() {
+    if line.starts_with('#') {
+      continue;
+    }
+    if line.starts_with('^') {
+      // the peeled object of the preceding annotated tag
+      let peeled = Oid::from_hex(line[1..].as_bytes())?;
+      if let Some(entry) = last.as_ref().and_then(|name| refs.get_mut(name)) {
+        entry.1 = Some(peeled);
+      }
+      continue;
+    }
+    let mut fields = line.splitn(2, ' ');
+    if let (Some(id), Some(name)) = (fields.next(), fields.next()) {
+      refs.insert(name.to_owned(), (Oid::from_hex(id.as_bytes())?, None));
+      last = Some(name.to_owned());
+    }
+  }
+  Ok(refs)
+}
+
+fn collect_loose_refs(dir: &Path, prefix: &str, refs: &mut Vec<(String, Oid)>) -> io::Result<()> {
+  let entries = match fs::read_dir(dir) {
+    Ok(entries) => entries,
+    Err(_) => return Ok(()),
+  };
+  for entry in entries {
+    let entry = entry?;
+    let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+    if entry.file_type()?.is_dir() {
+      collect_loose_refs(&entry.path(), &format!("{}/", name), refs)?;
+    } else {
+      refs.push((name, Oid::from_hex(read_content(entry.path())?.as_bytes())?));
+    }
+  }
+  Ok(())
+}
+
+fn shorten_ref(name: &str) -> String {
+  for prefix in &["refs/heads/", "refs/remotes/", "refs/tags/", "refs/"] {
+    if name.starts_with(prefix) {
+      return name[prefix.len()..].to_owned();
+    }
+  }
+  name.to_owned()
+}
+
+fn hash_blob(content: &[u8]) -> Oid {
+  let mut h = ::sha1_smol::Sha1::new();
+  h.update(format!("blob {}\0", content.len()).as_bytes());
+  h.update(content);
+  Oid(h.digest().bytes())
+}
+
+
+pub fn current_status(wd: &Path) -> io::Result<Option<Status>> {
+  let repo = Repo::open(wd)?;
+  let mut status = Status::default();
+
+  // get branch information, in the same manner as `git status --branch`.
+  let head = read_content(repo.git_dir.join("HEAD"))?;
+  let head_id = repo.resolve("HEAD")?;
+  let on_branch = head.starts_with("ref: refs/heads/") && head_id.is_some();
+  if on_branch {
+    let branch = &head[16..];
+    status.branch = branch.to_owned();
+    if let Some((tracking, upstream)) = repo.upstream(branch) {
+      match repo.resolve(&tracking)? {
+        Some(tracking_id) => {
+          let (ahead, behind) = repo.count_divergence(head_id.unwrap(), tracking_id)?;
+          status.upstream = upstream;
+          status.ahead_by = ahead;
+          status.behind_by = behind;
+        }
+        // the upstream is gone
+        None => status.branch = String::new(),
+      }
+    }
+  }
+  if status.branch == "" {
+    status.branch = get_branch(&repo, &head)?;
+  }
+
+  let index = Index::read(&repo.git_dir.join("index"))?;
+  if index.entries.iter().any(|e| e.mode == MODE_GITLINK || e.path.ends_with(".gitattributes")) {
+    return Err(unsupported("submodules or attributes"));
+  }
+  let (index_diff, working_diff) = compare(&repo, &index, head_id)?;
+  if index_diff.added > 0 || index_diff.modified > 0 || index_diff.deleted > 0 ||
+     index_diff.unmerged > 0 {
+    status.index = Some(index_diff);
+  }
+  if working_diff.added > 0 || working_diff.modified > 0 || working_diff.deleted > 0 ||
+     working_diff.unmerged > 0 {
+    status.working = Some(working_diff);
+  }
+  status.untracked = count_untracked(&repo, &index)?;
+
+  // collect stash count.
+  if repo.resolve("refs/stash")?.is_some() {
+    status.stash_count = read_content(repo.git_dir.join("logs/refs/stash"))
+      .unwrap_or_default()
+      .lines()
+      .filter(|line| !line.is_empty())
+      .count();
+  }
+  status.operation = get_operation(&repo.git_dir).map(ToOwned::to_owned);
+  // linked worktrees and submodules are left to the subprocess.
+  status.sparse = is_sparse(&repo.git_dir);
+
+  Ok(Some(status))
+}
+
+// the name of branch, or the tag or hash in parentheses if detached.
+fn get_branch(repo: &Repo, head: &str) -> io::Result<String> {
+  let r = get_operation(&repo.git_dir).map(|op| format!("|{}", op)).unwrap_or_default();
+
+  let b = if repo.git_dir.join("rebase-merge").exists() {
+    read_content(repo.git_dir.join("rebase-merge/head-name"))?
+  } else if head.starts_with("ref: ") {
+    head[5..].to_owned()
+  } else {
+    let head_id = Oid::from_hex(head.as_bytes())?;
+    let tag = repo.tags()?.into_iter().find(|&(_, id)| id == head_id).map(|(name, _)| name);
+    match tag {
+      Some(tag) => format!("({})", tag),
+      None if head.len() >= 7 => format!("({}...)", &head[..7]),
+      None => "(unknown)".to_owned(),
+    }
+  };
+
+  Ok(format!("{}{}", b.replace("refs/heads/", ""), r))
+}
+
+// compare the index with HEAD, and the working tree with the index.
+fn compare(repo: &Repo, index: &Index, head: Option<Oid>) -> io::Result<(DiffInfo, DiffInfo)> {
+  let mut staged = DiffInfo::default();
+  let mut working = DiffInfo::default();
+
+  let mut tree = BTreeMap::new();
+  let mut cached = false;
+  if let Some(head) = head {
+    let head_tree = repo.commit(&head)?.tree;
+    // the cache-tree tells that nothing is staged without reading trees.
+    cached = index.tree == Some(head_tree);
+    if !cached {
+      repo.flatten_tree(&head_tree, "", &mut tree)?;
+    }
+  }
+
+  // unmerged paths: the stages which are present
+  let mut conflicts: BTreeMap<&str, u8> = BTreeMap::new();
+  for entry in index.entries.iter().filter(|e| e.stage > 0) {
+    *conflicts.entry(&entry.path).or_insert(0) |= 1 << (entry.stage - 1);
+  }
+  for &stages in conflicts.values() {
+    // `git status --short`: DD, AU, UD, UA, DU, AA, UU
+    let (x, y) = match stages {
+      0b001 => ('D', 'D'),
+      0b010 => ('A', 'U'),
+      0b011 => ('U', 'D'),
+      0b100 => ('U', 'A'),
+      0b101 => ('D', 'U'),
+      0b110 => ('A', 'A'),
+      _ => ('U', 'U'),
+    };
+    count(&mut staged, x);
+    count(&mut working, y);
+  }
+
+  let mut added = Vec::new();
+  let mut tracked = BTreeSet::new();
+  let racy_limit = index.mtime;
+  for entry in index.entries.iter().filter(|e| e.stage == 0) {
+    tracked.insert(entry.path.as_str());
+    if entry.intent_to_add {
+      working.added += 1;
+      continue;
+    }
+
+    if !cached {
+      match tree.get(&entry.path) {
+        None => added.push(entry.id),
+        Some(&(mode, id)) => {
+          if mode & 0o170000 != entry.mode & 0o170000 {
+            // type changed, which is not counted
+          } else if id != entry.id || mode != entry.mode {
+            staged.modified += 1;
+          }
+        }
+      }
+    }
+
+    if entry.skip_worktree || entry.assume_valid {
+      continue;
+    }
+    let path = repo.worktree.join(&entry.path);
+    let meta = match fs::symlink_metadata(&path) {
+      Ok(meta) => meta,
+      Err(_) => {
+        working.deleted += 1;
+        continue;
+      }
+    };
+
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+      working.deleted += 1;
+      continue;
+    }
+    let is_link = entry.mode & 0o170000 == 0o120000;
+    if file_type.is_symlink() != is_link {
+      // type changed, which is not counted
+      continue;
+    }
+    if !is_link && cfg!(unix) && repo.config_bool("core", "fileMode") != Some(false) &&
+       (entry.mode & 0o100 != 0) != is_executable(&meta) {
+      working.modified += 1;
+      continue;
+    }
+
+    let mtime = mtime_of(&meta);
+    let racy = mtime >= racy_limit;
+    if !racy && meta.len() as u32 == entry.size && mtime == entry.mtime {
+      continue;
+    }
+    let content = if is_link {
+      fs::read_link(&path)?.to_string_lossy().into_owned().into_bytes()
+    } else {
+      fs::read(&path)?
+    };
+    if hash_blob(&content) != entry.id {
+      working.modified += 1;
+    }
+  }
+
+  // files removed from the index, except the unmerged ones
+  let mut deleted: Vec<Oid> = tree.iter()
+    .filter(|&(path, _)| !tracked.contains(path.as_str()) && !conflicts.contains_key(path.as_str()))
+    .map(|(_, &(_, id))| id)
+    .collect();
+
+  // pairs of deleted and added files with the same content are detected as renames.
+  let mut unpaired = Vec::new();
+  for id in added {
+    match deleted.iter().position(|d| *d == id) {
+      Some(pos) => {
+        deleted.swap_remove(pos);
+        staged.renamed += 1;
+      }
+      None => unpaired.push(id),
+    }
+  }
+  if !unpaired.is_empty() && !deleted.is_empty() {
+    // the rest may be renames with changes, which `git status` detects by the similarity of
+    // contents. it skips pairs whose sizes differ by more than a half, as done here.
+    let sizes = |ids: &[Oid]| -> io::Result<Vec<usize>> {
+      ids.iter().map(|id| repo.objects.read(id).map(|obj| obj.data.len())).collect()
+    };
+    let deleted_sizes = sizes(&deleted)?;
+    for a in sizes(&unpaired)? {
+      if deleted_sizes.iter().any(|&d| (a.max(d) - a.min(d)) * 2 <= a.max(d)) {
+        return Err(unsupported("renames with changes"));
+      }
+    }
+  }
+  staged.added += unpaired.len();
+  staged.deleted += deleted.len();
+
+  Ok((staged, working))
+}
+
+fn count(diff: &mut DiffInfo, c: char) {
+  match c {
+    'A' => diff.added += 1,
+    'D' => diff.deleted += 1,
+    'U' => diff.unmerged += 1,
+    _ => (),
+  }
+}
+
+// the number of untracked files, where a directory without tracked files is counted as one.
+fn count_untracked(repo: &Repo, index: &Index) -> io::Result<usize> {
+  let tracked: HashSet<&str> = index.entries.iter().map(|e| e.path.as_str()).collect();
+  let mut tracked_dirs = HashSet::new();
+  for path in &tracked {
+    let mut path: &str = path;
+    while let Some(pos) = path.rfind('/') {
+      path = &path[..pos];
+      if !tracked_dirs.insert(path) {
+        break;
+      }
+    }
+  }
+
+  let mut walker = Walker {
+    worktree: &repo.worktree,
+    tracked: tracked,
+    tracked_dirs: tracked_dirs,
+    ignore: Ignore::new(&repo.git_dir, repo.excludes_file().as_ref().map(|p| p.as_path())),
+  };
+  walker.count("")
+}
+
+struct Walker<'a> {
+  worktree: &'a Path,
+  tracked: HashSet<&'a str>,
+  tracked_dirs: HashSet<&'a str>,
+  ignore: Ignore,
+}
+
+impl<'a> Walker<'a> {
+  // list the entries of the directory, except `.git`.
+  fn entries(&self, dir: &str) -> io::Result<Vec<(String, bool)>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(self.worktree.join(dir))? {
+      let entry = entry?;
+      let name = entry.file_name().to_string_lossy().into_owned();
+      if name == ".git" {
+        continue;
+      }
+      if name == ".gitattributes" {
+        return Err(unsupported("attributes"));
+      }
+      let path = if dir.is_empty() {
+        name
+      } else {
+        format!("{}/{}", dir, name)
+      };
+      entries.push((path, entry.file_type()?.is_dir()));
+    }
+    Ok(entries)
+  }
+
+  fn count(&mut self, dir: &str) -> io::Result<usize> {
+    self.ignore.push(self.worktree, dir);
+    let mut count = 0;
+    for (path, is_dir) in self.entries(dir)? {
+      if self.tracked.contains(path.as_str()) || self.ignore.is_ignored(&path, is_dir) {
+        continue;
+      }
+      if !is_dir {
+        count += 1;
+      } else if self.tracked_dirs.contains(path.as_str()) {
+        count += self.count(&path)?;
+      } else if self.has_untracked(&path)? {
+        count += 1;
+      }
+    }
+    self.ignore.pop();
+    Ok(count)
+  }
+
+  fn has_untracked(&mut self, dir: &str) -> io::Result<bool> {
+    if self.worktree.join(dir).join(".git").exists() {
+      // a nested repository
+      return Ok(true);
+    }
+    self.ignore.push(self.worktree, dir);
+    let mut found = false;
+    for (path, is_dir) in self.entries(dir)? {
+      if self.ignore.is_ignored(&path, is_dir) {
+        continue;
+      }
+      if !is_dir || self.has_untracked(&path)? {
+        found = true;
+        break;
+      }
+    }
+    self.ignore.pop();
+    Ok(found)
+  }
+}
+
+
+#[cfg(test)]
+mod test_fixtures {
+  use std::fs;
+  use std::path::Path;
+  use rustc_serialize::json;
+  use vcs::git::{command_status, Status};
+  use super::{current_status, Repo};
+  use testing::{self, git, TempDir};
+
+  fn fixture(name: &str) -> TempDir {
+    ::testing::fixture(&format!("git/{}", name))
+  }
+
+  // the status read in-process, which must be the same as the one by `git status`.
+  fn status(dir: &Path) -> Status {
+    let status = current_status(dir).unwrap().unwrap();
+    if testing::available("git") {
+      let command = command_status(dir).unwrap().unwrap();
+      assert_eq!(json::encode(&status).unwrap(), json::encode(&command).unwrap());
+    }
+    status
+  }
+
+  #[test]
+  fn changes() {
+    let dir = fixture("changes");
+    let status = status(&dir);
+    assert_eq!(status.branch, "main");
+    let index = status.index.unwrap();
+    assert_eq!((index.added, index.modified, index.renamed, index.deleted), (1, 1, 1, 1));
+    let working = status.working.unwrap();
+    assert_eq!((working.added, working.modified, working.deleted), (1, 2, 1));
+    assert_eq!(status.untracked, 1);
+    assert_eq!(status.stash_count, 1);
+  }
+
+  #[test]
+  fn untracked_and_ignored() {
+    let dir = fixture("ignored");
+    // directories which cannot be checked in: empty ones, and a nested repository
+    fs::create_dir_all(dir.join("empty/dir")).unwrap();
+    fs::create_dir_all(dir.join("nested/.git/objects")).unwrap();
+    fs::create_dir_all(dir.join("nested/.git/refs")).unwrap();
+    fs::write(dir.join("nested/.git/HEAD"), "ref: refs/heads/main\n").unwrap();
+
+    let status = status(&dir);
+    assert_eq!(status.untracked, 5);
+    assert!(status.index.is_none() && status.working.is_none());
+  }
+
+  #[test]
+  fn detached_head() {
+    // packed objects and refs
+    let dir = fixture("detached");
+    let status = status(&dir);
+    assert_eq!(status.branch, "(light)");
+    assert!(status.index.is_none() && status.working.is_none());
+
+    // the annotated tag is peeled in `packed-refs`.
+    fs::write(dir.join(".git/HEAD"), "f4a70fe2a4392383c97aeed9c494e6545d211ef4\n").unwrap();
+    let status = self::status(&dir);
+    assert_eq!(status.branch, "(v1.0)");
+    assert_eq!(status.index.map(|d| d.modified), Some(1));
+    assert!(status.working.is_none());
+
+    fs::write(dir.join(".git/HEAD"), "60687b150ff89bd13cf6ed14cd43c2d1fce5601a\n").unwrap();
+    assert_eq!(self::status(&dir).branch, "(60687b1...)");
+  }
+
+  #[test]
+  fn ahead_and_behind() {
+    // trees are deltified in the pack, and the new commits are loose.
+    let dir = fixture("diverged");
+    let status = status(&dir);
+    assert_eq!(status.branch, "main");
+    assert_eq!(status.upstream, "origin/main");
+    assert_eq!((status.ahead_by, status.behind_by), (1, 2));
+
+    // the upstream branch is gone
+    fs::remove_file(dir.join(".git/refs/remotes/origin/main")).unwrap();
+    fs::write(dir.join(".git/packed-refs"),
+              "904f925e4f9bae3ae1b14b18a6a8238d40cf40ca refs/heads/main\n")
+      .unwrap();
+    let status = self::status(&dir);
+    assert_eq!(status.branch, "main");
+    assert_eq!(status.upstream, "");
+    assert_eq!((status.ahead_by, status.behind_by), (0, 0));
+  }
+
+  #[test]
+  fn unmerged() {
+    let dir = fixture("unmerged");
+    let status = status(&dir);
+    assert_eq!(status.operation, Some("MERGING".to_owned()));
+    let index = status.index.unwrap();
+    assert_eq!((index.added, index.unmerged), (1, 2));
+    let working = status.working.unwrap();
+    assert_eq!((working.added, working.deleted, working.unmerged), (1, 1, 1));
+  }
+
+  #[test]
+  fn initial_commit() {
+    let dir = fixture("initial");
+    let status = status(&dir);
+    assert_eq!(status.branch, "main");
+    assert_eq!(status.index.map(|d| d.added), Some(1));
+    assert!(status.working.is_none());
+    assert_eq!(status.untracked, 1);
+  }
+
+  #[test]
+  fn renames_with_changes() {
+    if !testing::available("git") {
+      return;
+    }
+    let dir = TempDir::new("git-native-renames");
+    let content: Vec<_> = (0..50).map(|n| n.to_string()).collect();
+    fs::write(dir.join("a.txt"), content.join("\n")).unwrap();
+    assert!(git(&dir, &["init", "--quiet"]));
+    assert!(git(&dir, &["add", "a.txt"]));
+    assert!(git(&dir, &["commit", "--quiet", "-m", "first"]));
+
+    // added and deleted files of different sizes are not renames.
+    fs::write(dir.join("b.txt"), "b").unwrap();
+    assert!(git(&dir, &["add", "b.txt"]));
+    assert!(git(&dir, &["rm", "--quiet", "--cached", "a.txt"]));
+    assert_eq!(status(&dir).index.map(|d| (d.added, d.deleted)), Some((1, 1)));
+
+    // `git status` decides by the similarity of contents.
+    assert!(git(&dir, &["rm", "--quiet", "--cached", "b.txt"]));
+    fs::write(dir.join("c.txt"), content.join("\n") + "\n50").unwrap();
+    assert!(git(&dir, &["add", "c.txt"]));
+    assert!(current_status(&dir).is_err());
+  }
+
+  #[test]
+  fn user_config() {
+    let dir = fixture("changes");
+    let home = TempDir::new("git-native-home");
+    let xdg = home.join(".config");
+    fs::create_dir_all(xdg.join("git")).unwrap();
+    let open = || Repo::open_with(&dir, Some(home.to_path_buf()), Some(xdg.clone()));
+    assert!(open().is_ok());
+
+    fs::write(home.join(".gitconfig"), "[status]\n\tshowUntrackedFiles = no\n").unwrap();
+    assert!(open().is_err());
+    fs::write(home.join(".gitconfig"), "[include]\n\tpath = ~/.gitconfig.local\n").unwrap();
+    assert!(open().is_err());
+    fs::remove_file(home.join(".gitconfig")).unwrap();
+
+    // the configuration of the repository takes precedence.
+    fs::write(xdg.join("git/config"), "[core]\n\tautocrlf = true\n").unwrap();
+    assert!(open().is_err());
+    let local = fs::read_to_string(dir.join(".git/config")).unwrap();
+    fs::write(dir.join(".git/config"), local + "[core]\n\tautocrlf = false\n").unwrap();
+    assert!(open().is_ok());
+  }
+}
@@ -0,0 +1,394 @@
+// Reading objects from the object database, either loose or packed.
+// blobs are never read since the working tree is compared with the index by hashes.
+
+use std::cell::RefCell;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use flate2::read::ZlibDecoder;
+use super::{Oid, invalid};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+  Commit,
+  Tree,
+  Blob,
+  Tag,
+}
+
+impl Kind {
+  fn from_name(name: &[u8]) -> io::Result<Kind> {
+    match name {
+      b"commit" => Ok(Kind::Commit),
+      b"tree" => Ok(Kind::Tree),
+      b"blob" => Ok(Kind::Blob),
+      b"tag" => Ok(Kind::Tag),
+      _ => Err(invalid("unknown object type")),
+    }
+  }
+
+  fn from_pack(code: u8) -> Option<Kind> {
+    match code {
+      1 => Some(Kind::Commit),
+      2 => Some(Kind::Tree),
+      3 => Some(Kind::Blob),
+      4 => Some(Kind::Tag),
+      _ => None,
+    }
+  }
+}
+
+pub struct Object {
+  pub kind: Kind,
+  pub data: Vec<u8>,
+}
+
+// A pair of `.idx` and `.pack`.
+struct Pack {
+  index: Vec<u8>,
+  file: RefCell<File>,
+}
+
+const OFS_DELTA: u8 = 6;
+const REF_DELTA: u8 = 7;
+
+impl Pack {
+  fn open(idx_path: &Path) -> io::Result<Pack> {
+    let mut index = Vec::new();
+    File::open(idx_path)?.read_to_end(&mut index)?;
+    // only the version 2 is written by Git since 1.5.2.
+    if index.len() < 8 + 256 * 4 || &index[..8] != b"\xfftOc\x00\x00\x00\x02" {
+      return Err(invalid("unsupported pack index"));
+    }
+    let file = File::open(idx_path.with_extension("pack"))?;
+    Ok(Pack {
+      index: index,
+      file: RefCell::new(file),
+    })
+  }
+
+  fn count(&self) -> usize {
+    read_u32(&self.index, 8 + 255 * 4) as usize
+  }
+
+  // the offset of the object in the pack file.
+  fn find(&self, oid: &Oid) -> Option<u64> {
+    let fanout = |i: usize| read_u32(&self.index, 8 + i * 4) as usize;
+    let names = 8 + 256 * 4;
+    let (mut lo, mut hi) = (if oid.0[0] == 0 { 0 } else { fanout(oid.0[0] as usize - 1) },
+                            fanout(oid.0[0] as usize));
+    while lo < hi {
+      let mid = (lo + hi) / 2;
+      let name = self.index.get(names + mid * 20..names + mid * 20 + 20)?;
+      match name.cmp(&oid.0[..]) {
+        ::std::cmp::Ordering::Less => lo = mid + 1,
+        ::std::cmp::Ordering::Greater => hi = mid,
+        ::std::cmp::Ordering::Equal => {
+          let n = self.count();
+          let offsets = names + n * 24;
+          let offset = read_u32(&self.index, offsets + mid * 4);
+          if offset & 0x8000_0000 == 0 {
+            return Some(offset as u64);
+          }
+          let large = offsets + n * 4 + (offset & 0x7fff_ffff) as usize * 8;
+          return Some((read_u32(&self.index, large) as u64) << 32 |
+                      read_u32(&self.index, large + 4) as u64);
+        }
+      }
+    }
+    None
+  }
+
+  fn read_at(&self, offset: u64, len: usize) -> io::Result<Vec<u8>> {
+    let mut file = self.file.borrow_mut();
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::with_capacity(len);
+    file.by_ref().take(len as u64).read_to_end(&mut buf)?;
+    Ok(buf)
+  }
+}
+
+
+pub struct ObjectDb {
+  dirs: Vec<PathBuf>,
+  packs: Vec<Pack>,
+}
+
+impl ObjectDb {
+  pub fn open(objects_dir: &Path) -> io::Result<ObjectDb> {
+    let mut dirs = vec![objects_dir.to_path_buf()];
+    // objects borrowed from other repositories, e.g. `git clone --reference`
+    if let Ok(content) = ::util::read_content(objects_dir.join("info/alternates")) {
+      for line in content.lines().filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        dirs.push(objects_dir.join(line));
+      }
+    }
+
+    let mut packs = Vec::new();
+    for dir in &dirs {
+      let entries = match fs::read_dir(dir.join("pack")) {
+        Ok(entries) => entries,
+        Err(_) => continue,
+      };
+      for entry in entries {
+        let path = entry?.path();
+        if path.extension().map(|e| e == "idx").unwrap_or(false) {
+          packs.push(Pack::open(&path)?);
+        }
+      }
+    }
+
+    Ok(ObjectDb {
+      dirs: dirs,
+      packs: packs,
+    })
+  }
+
+  pub fn read(&self, oid: &Oid) -> io::Result<Object> {
+    let hex = oid.to_hex();
+    for dir in &self.dirs {
+      let path = dir.join(&hex[..2]).join(&hex[2..]);
+      if let Ok(mut file) = File::open(path) {
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf)?;
+        return parse_loose(&decompress(&buf)?);
+      }
+    }
+
+    for pack in &self.packs {
+      if let Some(offset) = pack.find(oid) {
+        return self.read_packed(pack, offset, 0);
+      }
+    }
+
+    Err(io::Error::new(io::ErrorKind::NotFound, format!("object {} is not found", hex)))
+  }
+
+  fn read_packed(&self, pack: &Pack, offset: u64, depth: usize) -> io::Result<Object> {
+    if depth > 100 {
+      return Err(invalid("too deep delta chain"));
+    }
+
+    // the header: type and size, followed by the base of delta
+    let header = pack.read_at(offset, 32)?;
+    let mut pos = 0;
+    let mut byte = *header.get(pos).ok_or(invalid("truncated pack"))?;
+    let code = (byte >> 4) & 7;
+    let mut size = (byte & 0x0f) as usize;
+    let mut shift = 4;
+    while byte & 0x80 != 0 {
+      pos += 1;
+      byte = *header.get(pos).ok_or(invalid("truncated pack"))?;
+      size |= ((byte & 0x7f) as usize) << shift;
+      shift += 7;
+    }
+    pos += 1;
+
+    let base = match code {
+      OFS_DELTA => {
+        let mut byte = *header.get(pos).ok_or(invalid("truncated pack"))?;
+        let mut rel = (byte & 0x7f) as u64;
+        while byte & 0x80 != 0 {
+          pos += 1;
+          byte = *header.get(pos).ok_or(invalid("truncated pack"))?;
+          rel = ((rel + 1) << 7) | (byte & 0x7f) as u64;
+        }
+        pos += 1;
+        if rel > offset {
+          return Err(invalid("invalid delta offset"));
+        }
+        Some(self.read_packed(pack, offset - rel, depth + 1)?)
+      }
+      REF_DELTA => {
+        let base = header.get(pos..pos + 20).ok_or(invalid("truncated pack"))?;
+        pos += 20;
+        Some(self.read(&Oid::from_bytes(base))?)
+      }
+      _ => None,
+    };
+
+    // the compressed data never exceeds the original size plus the overhead of stored blocks.
+    let bound = size + size / 16000 * 5 + 64;
+    let data = decompress(&pack.read_at(offset + pos as u64, bound)?)?;
+    if data.len() != size {
+      return Err(invalid("size mismatch of packed object"));
+    }
+
+    match base {
+      Some(base) => {
+        Ok(Object {
+          kind: base.kind,
+          data: apply_delta(&base.data, &data)?,
+        })
+      }
+      None => {
+        Ok(Object {
+          kind: Kind::from_pack(code).ok_or(invalid("unknown object type"))?,
+          data: data,
+        })
+      }
+    }
+  }
+}
+
+// decompress a zlib stream. the trailing data after the end of stream is ignored.
+fn decompress(data: &[u8]) -> io::Result<Vec<u8>> {
+  let mut buf = Vec::with_capacity(data.len() * 2);
+  ZlibDecoder::new(data).read_to_end(&mut buf)?;
+  Ok(buf)
+}
+
+fn parse_loose(buf: &[u8]) -> io::Result<Object> {
+  let space = buf.iter().position(|&b| b == b' ').ok_or(invalid("invalid object header"))?;
+  let nul = buf.iter().position(|&b| b == 0).ok_or(invalid("invalid object header"))?;
+  if nul < space {
+    return Err(invalid("invalid object header"));
+  }
+  Ok(Object {
+    kind: Kind::from_name(&buf[..space])?,
+    data: buf[nul + 1..].to_vec(),
+  })
+}
+
+fn apply_delta(base: &[u8], delta: &[u8]) -> io::Result<Vec<u8>> {
+  fn varint(delta: &[u8], pos: &mut usize) -> io::Result<usize> {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+      let byte = *delta.get(*pos).ok_or(invalid("truncated delta"))?;
+      *pos += 1;
+      value |= ((byte & 0x7f) as usize) << shift;
+      shift += 7;
+      if byte & 0x80 == 0 {
+        return Ok(value);
+      }
+    }
+  }
+
+  let mut pos = 0;
+  if varint(delta, &mut pos)? != base.len() {
+    return Err(invalid("size mismatch of delta base"));
+  }
+  let size = varint(delta, &mut pos)?;
+
+  let mut result = Vec::with_capacity(size);
+  while pos < delta.len() {
+    let op = delta[pos];
+    pos += 1;
+    if op & 0x80 != 0 {
+      // copy from the base
+      let mut args = [0usize; 7];
+      for (i, arg) in args.iter_mut().enumerate() {
+        if op & (1 << i) != 0 {
+          *arg = *delta.get(pos).ok_or(invalid("truncated delta"))? as usize;
+          pos += 1;
+        }
+      }
+      let offset = args[0] | args[1] << 8 | args[2] << 16 | args[3] << 24;
+      let mut len = args[4] | args[5] << 8 | args[6] << 16;
+      if len == 0 {
+        len = 0x10000;
+      }
+      let chunk = base.get(offset..offset + len).ok_or(invalid("invalid delta copy"))?;
+      result.extend_from_slice(chunk);
+    } else if op != 0 {
+      // insert the following bytes
+      let chunk = delta.get(pos..pos + op as usize).ok_or(invalid("truncated delta"))?;
+      result.extend_from_slice(chunk);
+      pos += op as usize;
+    } else {
+      return Err(invalid("invalid delta opcode"));
+    }
+  }
+
+  if result.len() != size {
+    return Err(invalid("size mismatch of delta result"));
+  }
+  Ok(result)
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> u32 {
+  buf.get(pos..pos + 4)
+    .map(|b| (b[0] as u32) << 24 | (b[1] as u32) << 16 | (b[2] as u32) << 8 | b[3] as u32)
+    .unwrap_or(0)
+}
+
+
+// A commit, reduced to what is needed to count commits.
+pub struct Commit {
+  pub tree: Oid,
+  pub parents: Vec<Oid>,
+  pub time: i64,
+}
+
+impl Commit {
+  pub fn parse(data: &[u8]) -> io::Result<Commit> {
+    let mut tree = None;
+    let mut parents = Vec::new();
+    let mut time = 0;
+    for line in data.split(|&b| b == b'\n') {
+      if line.is_empty() {
+        // the end of headers
+        break;
+      }
+      if line.starts_with(b"tree ") {
+        tree = Some(Oid::from_hex(&line[5..])?);
+      } else if line.starts_with(b"parent ") {
+        parents.push(Oid::from_hex(&line[7..])?);
+      } else if line.starts_with(b"committer ") {
+        // `committer Name <email> 1234567890 +0900`
+        time = String::from_utf8_lossy(line)
+          .rsplitn(3, ' ')
+          .nth(1)
+          .and_then(|t| t.parse().ok())
+          .unwrap_or(0);
+      }
+    }
+    Ok(Commit {
+      tree: tree.ok_or(invalid("commit without tree"))?,
+      parents: parents,
+      time: time,
+    })
+  }
+}
+
+// the target of an annotated tag.
+pub fn tag_target(data: &[u8]) -> io::Result<Oid> {
+  data.split(|&b| b == b'\n')
+    .find(|line| line.starts_with(b"object "))
+    .ok_or(invalid("tag without object"))
+    .and_then(|line| Oid::from_hex(&line[7..]))
+}
+
+// the entries of a tree: `(mode, name, id)`.
+pub fn tree_entries(data: &[u8]) -> io::Result<Vec<(u32, Vec<u8>, Oid)>> {
+  let mut entries = Vec::new();
+  let mut pos = 0;
+  while pos < data.len() {
+    let space = pos +
+                data[pos..].iter().position(|&b| b == b' ').ok_or(invalid("invalid tree entry"))?;
+    let nul = space +
+              data[space..].iter().position(|&b| b == 0).ok_or(invalid("invalid tree entry"))?;
+    let mode = u32::from_str_radix(&String::from_utf8_lossy(&data[pos..space]), 8)
+      .map_err(|_| invalid("invalid tree entry"))?;
+    let id = data.get(nul + 1..nul + 21).ok_or(invalid("invalid tree entry"))?;
+    entries.push((mode, data[space + 1..nul].to_vec(), Oid::from_bytes(id)));
+    pos = nul + 21;
+  }
+  Ok(entries)
+}
+
+
+#[cfg(test)]
+mod test_delta {
+  use super::apply_delta;
+
+  #[test]
+  fn copy_and_insert() {
+    let base = b"hello, world";
+    // sizes (12, 11), copy 7 bytes from the offset 0, and insert 4 bytes.
+    let delta = [12, 11, 0x90, 7, 4, b'G', b'i', b't', b'!'];
+    assert_eq!(apply_delta(base, &delta).unwrap(), b"hello, Git!");
+    assert!(apply_delta(b"short", &delta).is_err());
+  }
+}
@@ -1,34 +1,83 @@
 // original implementation: https://github.com/JeremySkinner/posh-hg
 
 use std::io;
+use std::path::{Path, PathBuf};
 use regex::Regex;
-use vcs::Prompt;
+use url::Url;
+use prompt::{Fields, Symbols};
+use vcs::CloneOptions;
 use util::*;
 
-#[derive(Default)]
+#[derive(Default, RustcEncodable, RustcDecodable)]
 pub struct Status {
-  tags: Vec<String>,
-  commit: String,
-  branch: String,
-  behind: bool,
-  head_count: usize,
-  multiple_heads: bool,
-  active: String,
-  rev: String,
-  diff: Option<Diff>,
+  pub tags: Vec<String>,
+  pub commit: String,
+  pub branch: String,
+  pub behind: bool,
+  pub head_count: usize,
+  pub multiple_heads: bool,
+  pub active: String,
+  pub rev: String,
+  pub diff: Option<Diff>,
+  // patches of mq, if the repository has a queue
+  pub patches: Option<Patches>,
 }
 
-#[derive(Default)]
-struct Diff {
-  added: usize,
-  modified: usize,
-  deleted: usize,
-  untracked: usize,
-  missing: usize,
-  renamed: usize,
+#[derive(Default, RustcEncodable, RustcDecodable)]
+pub struct Diff {
+  pub added: usize,
+  pub modified: usize,
+  pub deleted: usize,
+  pub untracked: usize,
+  pub missing: usize,
+  pub renamed: usize,
 }
 
-pub fn current_status() -> io::Result<Option<Status>> {
+pub fn current_status(wd: &Path) -> io::Result<Option<Status>> {
+  // the in-process reader falls back on `hg summary` if it needs the contents of revlogs.
+  #[cfg(feature = "native-hg")]
+  let native = ::vcs::hg_native::current_status(wd).ok();
+  #[cfg(not(feature = "native-hg"))]
+  let native = None;
+
+  let mut status = match native {
+    Some(status) => status,
+    None => command_status(wd)?,
+  };
+  if let Some(ref mut status) = status {
+    // `hg qseries` is run only if mq has created the queue.
+    if wd.join(".hg/patches").is_dir() {
+      status.patches = Some(get_patches(wd)?);
+    }
+  }
+  Ok(status)
+}
+
+// the branch read from `.hg/branch` without running any command.
+// `wd` must be the root of the repository.
+pub fn partial_status(wd: &Path) -> io::Result<Option<Status>> {
+  if !wd.join(".hg").is_dir() {
+    return Ok(None);
+  }
+  // the file is absent on the default branch.
+  let branch = read_content(wd.join(".hg/branch")).unwrap_or_default();
+  Ok(Some(Status {
+    branch: if branch == "" { "default".to_owned() } else { branch },
+    ..Status::default()
+  }))
+}
+
+// files which are updated whenever the status is likely to change.
+pub fn key_files(wd: &Path) -> Vec<PathBuf> {
+  vec![wd.join(".hg/dirstate"),
+       wd.join(".hg/branch"),
+       wd.join(".hg/bookmarks.current"),
+       // updated by `hg pull`
+       wd.join(".hg/store/00changelog.i")]
+}
+
+// collect the status by running `hg summary`, `hg bookmarks` and `hg log`.
+pub fn command_status(wd: &Path) -> io::Result<Option<Status>> {
   let mut status = Status::default();
 
   let mut has_diff = false;
@@ -40,7 +89,7 @@ pub fn current_status() -> io::Result<Option<Status>> {
     let re2 = Regex::new(r"changeset:\s*(\S)").unwrap();
     let re3 = Regex::new(r"changeset:\s*(\S*)").unwrap();
 
-    let lines = get_lines("hg", &["parent"])?;
+    let lines = get_lines("hg", &["parent"], Some(wd))?;
     for line in lines {
       if let Some(m) = re1.captures(&line).and_then(|c| c.at(1)) {
         status.tags = m.replace("(empty repository)", "")
@@ -59,11 +108,11 @@ pub fn current_status() -> io::Result<Option<Status>> {
       }
     }
 
-    status.branch = get_lines("hg", &["branch"])?.into_iter().nth(0).unwrap();
+    status.branch = get_lines("hg", &["branch"], Some(wd))?.into_iter().nth(0).unwrap();
     status.behind = true;
     status.head_count = 0;
 
-    let lines = get_lines("hg", &["heads", &status.branch])?;
+    let lines = get_lines("hg", &["heads", &status.branch], Some(wd))?;
     for line in lines {
       if let Some(m) = re3.captures(&line).and_then(|c| c.at(1)) {
         if status.commit == m {
@@ -84,7 +133,7 @@ pub fn current_status() -> io::Result<Option<Status>> {
     let re5 = Regex::new(r"commit: (.*)").unwrap();
     let re6 = Regex::new(r"(\d+) (modified|added|removed|deleted|unknown|renamed)").unwrap();
 
-    let lines = get_lines("hg", &["summary"])?;
+    let lines = get_lines("hg", &["summary"], Some(wd))?;
     for line in lines {
       if let Some(cap) = re1.captures(&line) {
         if cap.len() > 2 {
@@ -153,7 +202,7 @@ pub fn current_status() -> io::Result<Option<Status>> {
 
   // getBookmarkStatus
   if true {
-    let lines = get_lines("hg", &["bookmarks"])?;
+    let lines = get_lines("hg", &["bookmarks"], Some(wd))?;
     for line in lines {
       if line.trim().starts_with("*") {
         status.active = line.split(" ").nth(2).unwrap().to_owned();
@@ -162,111 +211,172 @@ pub fn current_status() -> io::Result<Option<Status>> {
   }
 
   let lines = get_lines("hg",
-                        &["log", "-r", ".", "--template", "{rev}:{node|short}"])?;
+                        &["log", "-r", ".", "--template", "{rev}:{node|short}"],
+                        Some(wd))?;
   status.rev = lines[0].clone();
 
   Ok(Some(status))
 }
 
-impl Prompt for Status {
-  fn prompt(&self, _: bool) -> String {
-    let mut ret = String::new();
-
-    // show branch
-    ret += &self.branch;
-
-    // show diff
-    if let Some(ref diff) = self.diff {
-      ret += &format!("|+{}", diff.added);
-      ret += &format!(" ~{}", diff.modified);
-      ret += &format!(" x{}", diff.deleted);
-      ret += &format!(" ?{}", diff.untracked);
-      ret += &format!(" m{}", diff.missing);
-      ret += &format!(" c{}", diff.renamed);
-    }
-
-    // show tags
-    if self.tags.len() > 0 || self.active != "" {
-      ret += "|";
-      if self.active != "" {
-        ret += &self.active;
-        if self.tags.len() > 0 {
-          ret += " ";
+impl Fields for Status {
+  fn field(&self, name: &str, symbols: &Symbols) -> Option<String> {
+    let value = match name {
+      "branch" => self.branch.clone(),
+      "commit" => self.commit.clone(),
+      "rev" => self.rev.clone(),
+      "active" => self.active.clone(),
+      "tags" => self.tags.join(", "),
+      // the active bookmark and tags
+      "refs" => {
+        if self.active != "" && self.tags.len() > 0 {
+          format!("{} {}", self.active, self.tags.join(", "))
+        } else {
+          format!("{}{}", self.active, self.tags.join(", "))
         }
       }
-
-      for (i, ref tag) in self.tags.iter().enumerate() {
-        ret += tag;
-        if i < self.tags.len() - 1 {
-          ret += ", ";
+      "behind" => self.behind.to_string(),
+      "upstream_symbol" if self.behind => symbols.behind.clone(),
+      "upstream_symbol" => String::new(),
+      "head_count" => self.head_count.to_string(),
+      "multiple_heads" => self.multiple_heads.to_string(),
+      // patches of mq, collected along with the status
+      "patches" | "patches.applied" | "patches.unapplied" => {
+        let patches = match self.patches {
+          Some(ref patches) => patches,
+          None => return Some(String::new()),
+        };
+        match name {
+          "patches" => {
+            let all: Vec<_> = patches.applied.iter().chain(&patches.unapplied).cloned().collect();
+            all.join(", ")
+          }
+          "patches.applied" => patches.applied.join(", "),
+          _ => patches.unapplied.join(", "),
         }
       }
-    }
-
-    // show patches
-    if false {
-      // always disabled
-      if let Ok(patches) = get_patches("") {
-        if patches.all.len() > 0 {
-          ret += "|";
-
-          for (i, ref a) in patches.applied.iter().enumerate() {
-            ret += a;
-            if i < patches.applied.len() - 1 {
-              ret += ", ";
-            }
-          }
-          if patches.unapplied.len() > 0 {
-            ret += ", ";
-          }
-          for (i, ref u) in patches.unapplied.iter().enumerate() {
-            ret += u;
-            if i < patches.unapplied.len() - 1 {
-              ret += ", ";
-            }
-          }
+      "diff" => self.diff.is_some().to_string(),
+      _ if name.starts_with("diff.") => {
+        let clean = Diff::default();
+        let diff = self.diff.as_ref().unwrap_or(&clean);
+        match &name[5..] {
+          "added" => diff.added,
+          "modified" => diff.modified,
+          "deleted" => diff.deleted,
+          "untracked" => diff.untracked,
+          "missing" => diff.missing,
+          "renamed" => diff.renamed,
+          _ => return None,
         }
+        .to_string()
       }
-    }
+      _ => return None,
+    };
+    Some(value)
+  }
+}
+
+#[derive(Default, RustcEncodable, RustcDecodable)]
+pub struct Patches {
+  pub applied: Vec<String>,
+  pub unapplied: Vec<String>,
+}
+
+// run `hg qseries -v` in the repository at `wd`.
+fn get_patches(wd: &Path) -> io::Result<Patches> {
+  let lines = get_lines("hg", &["qseries", "-v"], Some(wd))?;
+  Ok(parse_patches(&lines))
+}
 
-    // show revision
-    if self.rev != "" {
-      ret += &format!(" <{}>", self.rev);
+// lines of `hg qseries -v`, e.g. `0 A fix-typo.patch`.
+// `A` is applied, and `U` (unapplied) or `G` (guarded) is not.
+fn parse_patches(lines: &[String]) -> Patches {
+  let mut patches = Patches::default();
+  for line in lines {
+    let mut columns = line.split_whitespace();
+    match (columns.next(), columns.next(), columns.next()) {
+      (Some(_), Some("A"), Some(name)) => patches.applied.push(name.to_owned()),
+      (Some(_), Some(_), Some(name)) => patches.unapplied.push(name.to_owned()),
+      _ => (),
     }
+  }
+  patches
+}
 
-    ret
+pub fn clone(url: &Url,
+             dest: &Path,
+             opts: &CloneOptions,
+             out: &mut Output)
+             -> Result<i32, io::Error> {
+  let mut args = vec!["clone", url.as_str(), dest.to_str().unwrap()];
+  if let Some(ref branch) = opts.branch {
+    args.extend(&["--branch", branch]);
   }
+  if opts.bare {
+    args.push("--noupdate");
+  }
+
+  out.exec("hg", args.as_slice(), None)
 }
 
-struct Patches {
-  all: Vec<String>,
-  applied: Vec<String>,
-  unapplied: Vec<String>,
+pub fn update(dest: &Path, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("hg", &["pull", "-u"], Some(dest))
 }
 
-fn get_patches(filter: &str) -> io::Result<Patches> {
-  let mut applied = Vec::new();
-  let mut unapplied = Vec::new();
+pub fn update_to(dest: &Path, revision: &str, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("hg", &["update", "--rev", revision], Some(dest))
+}
 
-  let lines = get_lines("hg", &["qseries", "-v"])?;
-  for line in lines {
-    let status = line.split(" ").nth(1).unwrap().to_owned();
-    let name = line.split(" ").nth(2).unwrap().to_owned();
-    if status == "A" {
-      applied.push(name);
-    } else {
-      unapplied.push(name);
-    }
+pub fn revision(dest: &Path) -> Result<Option<String>, io::Error> {
+  get_lines("hg",
+            &["-R", dest.to_str().unwrap(), "log", "-r", ".", "--template", "{node}"],
+            None)
+    .map(|lines| lines.into_iter().next())
+}
+
+// the number of changesets which are not published yet, i.e. in the draft or secret phase.
+pub fn unpushed_commits(dest: &Path) -> Result<Option<usize>, io::Error> {
+  let (stdout, _, status) = communicate("hg",
+                                        &["-R",
+                                          dest.to_str().unwrap(),
+                                          "log",
+                                          "-r",
+                                          "not public()",
+                                          "--template",
+                                          "{node}\\n"],
+                                        None)?;
+  if status != 0 {
+    return Ok(None);
   }
+  Ok(Some(stdout.lines().filter(|line| !line.is_empty()).count()))
+}
 
-  let mut all: Vec<_> = applied.iter().chain(unapplied.iter()).cloned().collect();
-  if filter != "" {
-    all = all.into_iter().filter(|a| a.starts_with(filter)).collect();
+// the path of `default`, read from `.hg/hgrc`.
+pub fn remote_url(dest: &Path) -> Result<Option<String>, io::Error> {
+  let hgrc = if dest.join(".hg").is_dir() {
+    dest.join(".hg/hgrc")
+  } else {
+    dest.join("hgrc")
   };
+  if !hgrc.is_file() {
+    return Ok(None);
+  }
+  read_content(hgrc).map(|content| ini_value(&content, "paths", "default"))
+}
+
 
-  Ok(Patches {
-    all: all,
-    applied: applied,
-    unapplied: unapplied,
-  })
+#[cfg(test)]
+mod test_patches {
+  use super::parse_patches;
+
+  #[test]
+  fn qseries() {
+    let lines: Vec<_> = [" 0 A first.patch", " 1 A second.patch", " 2 U third.patch",
+                         "10 G guarded.patch", ""]
+      .iter()
+      .map(|s| s.to_string())
+      .collect();
+    let patches = parse_patches(&lines);
+    assert_eq!(patches.applied, vec!["first.patch", "second.patch"]);
+    assert_eq!(patches.unapplied, vec!["third.patch", "guarded.patch"]);
+  }
 }
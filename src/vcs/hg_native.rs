@@ -0,0 +1,693 @@
+// An in-process reader of `hg::current_status()`, enabled by the feature `native-hg`.
+//
+// It reads `.hg/dirstate`, `.hg/branch`, `.hg/bookmarks.current` and the caches of tags and
+// branch heads instead of running `hg summary`, `hg bookmarks` and `hg log`.
+// The contents of revlogs are never read, so the status which depends on them (e.g. files
+// whose size is unchanged but whose modified time differs, or stale caches) is reported as an
+// error, and the caller falls back on the subprocess.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File, Metadata};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use regex::{self, Regex};
+use util::read_content;
+use vcs::hg::{Diff, Status};
+
+const NULL_NODE: [u8; 20] = [0; 20];
+
+fn unsupported(msg: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::Other, msg)
+}
+
+// the configuration files read by Mercurial on Unix: system-wide, per-user and the repository.
+// `$HGRCPATH` replaces the system-wide and per-user ones, and is left to the subprocess.
+#[cfg(unix)]
+fn config_files(hg_dir: &Path) -> io::Result<Vec<PathBuf>> {
+  use std::env;
+
+  if env::var_os("HGRCPATH").is_some() {
+    return Err(unsupported("HGRCPATH"));
+  }
+
+  let mut files = Vec::new();
+  for etc in &["/usr/local/etc/mercurial", "/etc/mercurial"] {
+    files.push(Path::new(etc).join("hgrc"));
+    if let Ok(entries) = fs::read_dir(Path::new(etc).join("hgrc.d")) {
+      let mut rc: Vec<_> = entries.filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|path| path.extension().map(|ext| ext == "rc").unwrap_or(false))
+        .collect();
+      rc.sort();
+      files.extend(rc);
+    }
+  }
+
+  let home = env::var_os("HOME").map(PathBuf::from);
+  let xdg = env::var_os("XDG_CONFIG_HOME")
+    .map(PathBuf::from)
+    .or(home.as_ref().map(|h| h.join(".config")));
+  if let Some(xdg) = xdg {
+    files.push(xdg.join("hg/hgrc"));
+  }
+  if let Some(home) = home {
+    files.push(home.join(".hgrc"));
+  }
+  files.push(hg_dir.join("hgrc"));
+  Ok(files)
+}
+
+// the locations depend on the installation on Windows.
+#[cfg(not(unix))]
+fn config_files(_: &Path) -> io::Result<Vec<PathBuf>> {
+  Err(unsupported("configuration files on Windows"))
+}
+
+// whether `ui.ignore` or `ui.ignore.<name>` is set, which add ignore files other than
+// `.hgignore`. the included files are not followed.
+fn has_ignore_files(content: &str) -> io::Result<bool> {
+  let mut in_ui = false;
+  for line in content.lines().map(|line| line.trim()) {
+    if line.starts_with("%include") {
+      return Err(unsupported("included configuration"));
+    }
+    if line.starts_with('[') && line.ends_with(']') {
+      in_ui = line[1..line.len() - 1].trim() == "ui";
+      continue;
+    }
+    let key = line.splitn(2, '=').next().unwrap_or_default().trim();
+    if in_ui && line.contains('=') && (key == "ignore" || key.starts_with("ignore.")) {
+      return Ok(true);
+    }
+  }
+  Ok(false)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn read_u32(buf: &[u8], pos: usize) -> u32 {
+  (buf[pos] as u32) << 24 | (buf[pos + 1] as u32) << 16 | (buf[pos + 2] as u32) << 8 |
+  buf[pos + 3] as u32
+}
+
+fn read_bytes(path: &Path) -> io::Result<Vec<u8>> {
+  let mut buf = Vec::new();
+  File::open(path)?.read_to_end(&mut buf)?;
+  Ok(buf)
+}
+
+
+// An entry of `.hg/dirstate`.
+struct Entry {
+  // `n` (normal), `a` (added), `r` (removed) or `m` (merged)
+  state: u8,
+  mode: u32,
+  size: i32,
+  mtime: i32,
+  copy_source: Option<String>,
+}
+
+struct Dirstate {
+  parents: ([u8; 20], [u8; 20]),
+  entries: BTreeMap<String, Entry>,
+}
+
+impl Dirstate {
+  // the format of version 1: two parents, followed by
+  // `<state:1><mode:4><size:4><mtime:4><length:4><name>[\0<copy source>]`.
+  fn read(path: &Path) -> io::Result<Dirstate> {
+    let buf = match read_bytes(path) {
+      Ok(buf) => buf,
+      Err(ref err) if err.kind() == io::ErrorKind::NotFound => {
+        return Ok(Dirstate {
+          parents: (NULL_NODE, NULL_NODE),
+          entries: BTreeMap::new(),
+        })
+      }
+      Err(err) => return Err(err),
+    };
+    if buf.len() < 40 {
+      return Err(unsupported("truncated dirstate"));
+    }
+
+    let mut parents = (NULL_NODE, NULL_NODE);
+    parents.0.copy_from_slice(&buf[..20]);
+    parents.1.copy_from_slice(&buf[20..40]);
+
+    let mut entries = BTreeMap::new();
+    let mut pos = 40;
+    while pos < buf.len() {
+      if pos + 17 > buf.len() {
+        return Err(unsupported("truncated dirstate"));
+      }
+      let len = read_u32(&buf, pos + 13) as usize;
+      let name = buf.get(pos + 17..pos + 17 + len).ok_or(unsupported("truncated dirstate"))?;
+      let name = String::from_utf8_lossy(name).into_owned();
+      let mut fields = name.splitn(2, '\0');
+      let path = fields.next().unwrap_or_default().to_owned();
+      entries.insert(path,
+                     Entry {
+                       state: buf[pos],
+                       mode: read_u32(&buf, pos + 1),
+                       size: read_u32(&buf, pos + 5) as i32,
+                       mtime: read_u32(&buf, pos + 9) as i32,
+                       copy_source: fields.next().map(ToOwned::to_owned),
+                     });
+      pos += 17 + len;
+    }
+
+    Ok(Dirstate {
+      parents: parents,
+      entries: entries,
+    })
+  }
+}
+
+
+struct Repo {
+  root: PathBuf,
+  hg_dir: PathBuf,
+  // the nodes of changesets, in the order of revision numbers
+  changelog: Vec<[u8; 20]>,
+}
+
+impl Repo {
+  fn open(wd: &Path) -> io::Result<Repo> {
+    let mut root = wd.to_path_buf();
+    while !root.join(".hg").is_dir() {
+      if !root.pop() {
+        return Err(unsupported("not a repository"));
+      }
+    }
+    let hg_dir = root.join(".hg");
+
+    let requires = read_content(hg_dir.join("requires")).unwrap_or_default();
+    if requires.lines().any(|r| r == "dirstate-v2" || r == "shared" || r == "relshared") ||
+       hg_dir.join("sharedpath").exists() || root.join(".hgsub").exists() {
+      return Err(unsupported("unsupported repository"));
+    }
+    for path in config_files(&hg_dir)? {
+      if let Ok(content) = read_content(&path) {
+        if has_ignore_files(&content)? {
+          return Err(unsupported("additional ignore files"));
+        }
+      }
+    }
+
+    let store = if requires.lines().any(|r| r == "store") {
+      hg_dir.join("store")
+    } else {
+      hg_dir.clone()
+    };
+    let changelog = read_changelog(&store.join("00changelog.i"))?;
+
+    Ok(Repo {
+      root: root,
+      hg_dir: hg_dir,
+      changelog: changelog,
+    })
+  }
+
+  fn rev(&self, node: &[u8; 20]) -> io::Result<i64> {
+    if *node == NULL_NODE {
+      return Ok(-1);
+    }
+    self.changelog
+      .iter()
+      .position(|n| n == node)
+      .map(|rev| rev as i64)
+      .ok_or(unsupported("unknown changeset"))
+  }
+
+  fn tip(&self) -> (i64, [u8; 20]) {
+    self.changelog
+      .last()
+      .map(|node| (self.changelog.len() as i64 - 1, *node))
+      .unwrap_or((-1, NULL_NODE))
+  }
+
+  // read the cache of tags, which is valid only if it is written at the current tip.
+  // the format is `<tiprev> <tipnode> [<filtered hash>]` followed by `<node> <name>`,
+  // where the last line of each name is the current one.
+  fn tags(&self) -> io::Result<BTreeMap<String, String>> {
+    let content = read_content(self.hg_dir.join("cache/tags2-visible"))
+      .map_err(|_| unsupported("no cache of tags"))?;
+    let mut lines = content.lines();
+    let header: Vec<_> = lines.next().unwrap_or_default().split(' ').collect();
+    let (tiprev, tipnode) = self.tip();
+    if header.len() < 2 || header[0] != tiprev.to_string() || header[1] != to_hex(&tipnode) {
+      return Err(unsupported("stale cache of tags"));
+    }
+
+    let mut tags = BTreeMap::new();
+    for line in lines {
+      let mut fields = line.splitn(2, ' ');
+      if let (Some(node), Some(name)) = (fields.next(), fields.next()) {
+        tags.insert(name.to_owned(), node.to_owned());
+      }
+    }
+    // local tags
+    for line in read_content(self.hg_dir.join("localtags")).unwrap_or_default().lines() {
+      let mut fields = line.splitn(2, ' ');
+      if let (Some(node), Some(name)) = (fields.next(), fields.next()) {
+        tags.insert(name.trim().to_owned(), node.to_owned());
+      }
+    }
+
+    let null = to_hex(&NULL_NODE);
+    tags.insert("tip".to_owned(), to_hex(&tipnode));
+    Ok(tags.into_iter().filter(|&(_, ref node)| *node != null).collect())
+  }
+
+  // the open heads of the branch, read from the cache of branch heads.
+  // the format is `<tipnode> <tiprev> [<filtered hash>]` followed by `<node> <o|c> <branch>`.
+  fn branch_heads(&self, branch: &str) -> io::Result<Vec<String>> {
+    let (tiprev, tipnode) = self.tip();
+    for filter in &["visible", "served", "immutable", "base"] {
+      let content = match read_content(self.hg_dir.join(format!("cache/branch2-{}", filter))) {
+        Ok(content) => content,
+        Err(_) => continue,
+      };
+      let mut lines = content.lines();
+      let header: Vec<_> = lines.next().unwrap_or_default().split(' ').collect();
+      // a cache of filtered changesets does not tell the visible heads.
+      if header.len() != 2 || header[0] != to_hex(&tipnode) || header[1] != tiprev.to_string() {
+        continue;
+      }
+
+      return Ok(lines.filter_map(|line| {
+          let fields: Vec<_> = line.splitn(3, ' ').collect();
+          if fields.len() == 3 && fields[1] == "o" && fields[2] == branch {
+            Some(fields[0].to_owned())
+          } else {
+            None
+          }
+        })
+        .collect());
+    }
+
+    if self.changelog.is_empty() {
+      Ok(Vec::new())
+    } else {
+      Err(unsupported("no cache of branch heads"))
+    }
+  }
+}
+
+// the nodes of revisions in the index of changelog (RevlogNG, possibly with inline data).
+fn read_changelog(path: &Path) -> io::Result<Vec<[u8; 20]>> {
+  let buf = match read_bytes(path) {
+    Ok(buf) => buf,
+    Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+    Err(err) => return Err(err),
+  };
+  if buf.len() < 64 {
+    return Ok(Vec::new());
+  }
+
+  let header = read_u32(&buf, 0);
+  if header & 0xffff != 1 {
+    return Err(unsupported("unsupported format of revlog"));
+  }
+  let inline = header & (1 << 16) != 0;
+
+  let mut nodes = Vec::new();
+  let mut pos = 0;
+  while pos + 64 <= buf.len() {
+    let mut node = NULL_NODE;
+    node.copy_from_slice(&buf[pos + 32..pos + 52]);
+    nodes.push(node);
+    pos += 64;
+    if inline {
+      pos += read_u32(&buf, pos - 64 + 8) as usize;
+    }
+  }
+  Ok(nodes)
+}
+
+
+// The patterns of `.hgignore`, translated into regular expressions.
+struct Ignore {
+  patterns: Vec<Regex>,
+}
+
+impl Ignore {
+  fn read(root: &Path) -> io::Result<Ignore> {
+    let content = read_content(root.join(".hgignore")).unwrap_or_default();
+    let mut patterns = Vec::new();
+    let mut syntax = "relre";
+    for line in content.lines() {
+      let line = line.find('#').map(|pos| &line[..pos]).unwrap_or(line).trim_right();
+      if line.is_empty() {
+        continue;
+      }
+      if line.starts_with("syntax:") {
+        syntax = match line[7..].trim() {
+          "re" | "regexp" => "relre",
+          "glob" => "relglob",
+          _ => return Err(unsupported("unknown syntax of .hgignore")),
+        };
+        continue;
+      }
+
+      let (kind, pattern) = match line.find(':') {
+        Some(pos) => {
+          match &line[..pos] {
+            "re" | "regexp" | "relre" => ("relre", &line[pos + 1..]),
+            "glob" | "relglob" => ("relglob", &line[pos + 1..]),
+            "rootglob" => ("rootglob", &line[pos + 1..]),
+            "include" | "subinclude" | "path" | "relpath" | "rootfilesin" | "listfile" => {
+              return Err(unsupported("unsupported kind of pattern in .hgignore"))
+            }
+            _ => (syntax, line),
+          }
+        }
+        None => (syntax, line),
+      };
+
+      let re = match kind {
+        "relre" if pattern.starts_with('^') => pattern.to_owned(),
+        "relre" => format!(".*(?:{})", pattern),
+        "relglob" => format!("(?:.*/)?{}(?:/|$)", glob_to_regex(pattern)),
+        _ => format!("{}(?:/|$)", glob_to_regex(pattern)),
+      };
+      patterns.push(Regex::new(&format!("^(?:{})", re))
+        .map_err(|_| unsupported("invalid pattern in .hgignore"))?);
+    }
+
+    Ok(Ignore { patterns: patterns })
+  }
+
+  fn is_ignored(&self, path: &str) -> bool {
+    self.patterns.iter().any(|re| re.is_match(path))
+  }
+}
+
+// translate a glob in the manner of Mercurial, e.g. `**/` matches any directories.
+fn glob_to_regex(pattern: &str) -> String {
+  let chars: Vec<char> = pattern.chars().collect();
+  let mut res = String::new();
+  let mut group = 0;
+  let mut i = 0;
+  while i < chars.len() {
+    let c = chars[i];
+    i += 1;
+    match c {
+      '*' if chars.get(i) == Some(&'*') => {
+        i += 1;
+        if chars.get(i) == Some(&'/') {
+          i += 1;
+          res += "(?:.*/)?";
+        } else {
+          res += ".*";
+        }
+      }
+      '*' => res += "[^/]*",
+      '?' => res += ".",
+      '[' => {
+        let mut j = i;
+        if j < chars.len() && (chars[j] == '!' || chars[j] == ']') {
+          j += 1;
+        }
+        while j < chars.len() && chars[j] != ']' {
+          j += 1;
+        }
+        if j >= chars.len() {
+          res += "\\[";
+        } else {
+          let class: String = chars[i..j].iter().collect();
+          let class = class.replace("\\", "\\\\");
+          i = j + 1;
+          if class.starts_with('!') {
+            res += &format!("[^{}]", &class[1..]);
+          } else if class.starts_with('^') {
+            res += &format!("[\\{}]", class);
+          } else {
+            res += &format!("[{}]", class);
+          }
+        }
+      }
+      '{' => {
+        group += 1;
+        res += "(?:";
+      }
+      '}' if group > 0 => {
+        group -= 1;
+        res += ")";
+      }
+      ',' if group > 0 => res += "|",
+      '\\' if i < chars.len() => {
+        res += &regex::quote(&chars[i].to_string());
+        i += 1;
+      }
+      c => res += &regex::quote(&c.to_string()),
+    }
+  }
+  res
+}
+
+
+pub fn current_status(wd: &Path) -> io::Result<Option<Status>> {
+  let repo = Repo::open(wd)?;
+  let dirstate = Dirstate::read(&repo.hg_dir.join("dirstate"))?;
+  if dirstate.parents.1 != NULL_NODE {
+    // merging
+    return Err(unsupported("the working directory has two parents"));
+  }
+  let parent = dirstate.parents.0;
+
+  let mut status = Status::default();
+  status.branch = read_content(repo.hg_dir.join("branch"))
+    .ok()
+    .and_then(|b| if b.is_empty() { None } else { Some(b) })
+    .unwrap_or("default".to_owned());
+
+  let rev = format!("{}:{}", repo.rev(&parent)?, &to_hex(&parent)[..12]);
+  status.commit = rev.clone();
+  status.rev = rev;
+  let parent_hex = to_hex(&parent);
+  status.tags = repo.tags()?
+    .into_iter()
+    .filter(|&(_, ref node)| *node == parent_hex)
+    .map(|(name, _)| name)
+    .collect();
+
+  // `update: N new changesets` unless the parent is the only head of the branch.
+  let heads = repo.branch_heads(&status.branch)?;
+  status.behind = !heads.is_empty() && heads != vec![parent_hex];
+
+  let diff = compare(&repo, &dirstate)?;
+  if diff.added > 0 || diff.modified > 0 || diff.deleted > 0 || diff.untracked > 0 ||
+     diff.missing > 0 || diff.renamed > 0 {
+    status.diff = Some(diff);
+  }
+
+  // the active bookmark, if it exists.
+  if let Ok(active) = read_content(repo.hg_dir.join("bookmarks.current")) {
+    let bookmarks = read_content(repo.hg_dir.join("bookmarks")).unwrap_or_default();
+    if bookmarks.lines().any(|line| line.splitn(2, ' ').nth(1) == Some(&active)) {
+      status.active = active;
+    }
+  }
+
+  Ok(Some(status))
+}
+
+#[cfg(unix)]
+fn is_executable(meta: &Metadata) -> bool {
+  use std::os::unix::fs::PermissionsExt;
+  meta.permissions().mode() & 0o100 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_: &Metadata) -> bool {
+  false
+}
+
+#[cfg(unix)]
+fn mtime_of(meta: &Metadata) -> i32 {
+  use std::os::unix::fs::MetadataExt;
+  (meta.mtime() & 0x7fffffff) as i32
+}
+
+#[cfg(not(unix))]
+fn mtime_of(meta: &Metadata) -> i32 {
+  meta.modified()
+    .ok()
+    .and_then(|t| t.duration_since(::std::time::UNIX_EPOCH).ok())
+    .map(|d| (d.as_secs() & 0x7fffffff) as i32)
+    .unwrap_or(0)
+}
+
+// compare the working directory with the dirstate, in the same manner as `hg summary`.
+fn compare(repo: &Repo, dirstate: &Dirstate) -> io::Result<Diff> {
+  let mut modified = BTreeSet::new();
+  let mut added = BTreeSet::new();
+  let mut removed = BTreeSet::new();
+  let mut deleted = 0;
+
+  for (path, entry) in &dirstate.entries {
+    let meta = fs::symlink_metadata(repo.root.join(path)).ok().filter(|m| !m.is_dir());
+    match (entry.state, meta) {
+      (b'r', _) => {
+        removed.insert(path.as_str());
+      }
+      (_, None) => deleted += 1,
+      (b'a', _) => {
+        added.insert(path.as_str());
+      }
+      (b'n', Some(meta)) => {
+        if entry.size < 0 || entry.mtime < 0 {
+          // possibly dirty, or taken from the other parent
+          return Err(unsupported("the content of file is needed"));
+        }
+        let is_link = entry.mode & 0o170000 == 0o120000;
+        let exec_changed = !is_link && cfg!(unix) &&
+                           (entry.mode & 0o100 != 0) != is_executable(&meta);
+        if is_link != meta.file_type().is_symlink() || exec_changed ||
+           (meta.len() & 0x7fffffff) as i32 != entry.size {
+          modified.insert(path.as_str());
+        } else if mtime_of(&meta) != entry.mtime {
+          // the file must be compared with the revision in the revlog.
+          return Err(unsupported("the content of file is needed"));
+        }
+      }
+      _ => return Err(unsupported("unknown state in dirstate")),
+    }
+  }
+
+  // copies whose sources are removed are counted as renames.
+  let mut renamed = 0;
+  for (path, entry) in &dirstate.entries {
+    if let Some(ref source) = entry.copy_source {
+      if removed.remove(source.as_str()) {
+        renamed += 1;
+      }
+      added.remove(path.as_str());
+    }
+  }
+
+  let ignore = Ignore::read(&repo.root)?;
+  let untracked = count_unknown(&repo.root, "", &dirstate, &ignore)?;
+
+  Ok(Diff {
+    added: added.len(),
+    modified: modified.len(),
+    deleted: removed.len(),
+    untracked: untracked,
+    missing: deleted,
+    renamed: renamed,
+  })
+}
+
+// the number of files neither tracked nor ignored.
+fn count_unknown(root: &Path,
+                 dir: &str,
+                 dirstate: &Dirstate,
+                 ignore: &Ignore)
+                 -> io::Result<usize> {
+  let mut count = 0;
+  for entry in fs::read_dir(root.join(dir))? {
+    let entry = entry?;
+    let name = entry.file_name().to_string_lossy().into_owned();
+    let path = if dir.is_empty() {
+      name.clone()
+    } else {
+      format!("{}/{}", dir, name)
+    };
+
+    if entry.file_type()?.is_dir() {
+      // nested repositories are not walked into.
+      if name == ".hg" || entry.path().join(".hg").is_dir() || ignore.is_ignored(&path) {
+        continue;
+      }
+      count += count_unknown(root, &path, dirstate, ignore)?;
+    } else if !dirstate.entries.contains_key(&path) && !ignore.is_ignored(&path) {
+      count += 1;
+    }
+  }
+  Ok(count)
+}
+
+
+#[cfg(test)]
+mod test_fixtures {
+  use std::path::Path;
+  use filetime::{self, FileTime};
+  use rustc_serialize::json;
+  use super::{current_status, glob_to_regex, has_ignore_files};
+  use testing::{self, TempDir};
+  use vcs::hg::{command_status, Status};
+
+  // the modified time recorded in the dirstate of fixtures
+  const MTIME: i64 = 1500000000;
+
+  // copy a fixture into a temporary directory, with the files touched at the recorded time.
+  fn fixture(name: &str, touch: &[&str]) -> TempDir {
+    let dir = testing::fixture(&format!("hg/{}", name));
+    for path in touch {
+      filetime::set_file_mtime(dir.join(path), FileTime::from_unix_time(MTIME, 0)).unwrap();
+    }
+    dir
+  }
+
+  // the status must be the same as the one from `hg summary`, if Mercurial is installed.
+  fn compare_with_command(wd: &Path, status: &Status) {
+    if testing::available("hg") {
+      let command = command_status(wd).unwrap().unwrap();
+      assert_eq!(json::encode(status).unwrap(), json::encode(&command).unwrap());
+    }
+  }
+
+  #[test]
+  fn globs() {
+    assert_eq!(glob_to_regex("*.o"), r"[^/]*\.o");
+    assert_eq!(glob_to_regex("**/build"), "(?:.*/)?build");
+    assert_eq!(glob_to_regex("{a,b}[!c]?"), "(?:a|b)[^c].");
+  }
+
+  #[test]
+  fn ignore_files() {
+    assert!(has_ignore_files("[ui]\nignore = ~/.hgignore_global\n").unwrap());
+    assert!(has_ignore_files("[ui]\nusername = a\nignore.work = ~/.hgignore_work\n").unwrap());
+    assert!(!has_ignore_files("[ui]\nusername = a\n[extensions]\nignore = \n").unwrap());
+    assert!(has_ignore_files("%include ~/.hgrc.local\n").is_err());
+  }
+
+  #[test]
+  fn changes() {
+    let dir = fixture("changes", &[".hgignore", "a.txt", "b.txt", "sub/g.txt"]);
+
+    let status = current_status(&dir.join("sub")).unwrap().unwrap();
+    assert_eq!(status.branch, "default");
+    assert_eq!(status.active, "feature");
+    assert_eq!(status.tags, vec!["tip".to_owned(), "v2.0".to_owned()]);
+    assert!(!status.behind);
+    assert!(status.rev.starts_with("2:"));
+    assert_eq!(status.commit, status.rev);
+    let counts = status.diff
+      .as_ref()
+      .map(|d| (d.added, d.modified, d.deleted, d.untracked, d.missing, d.renamed));
+    assert_eq!(counts, Some((1, 1, 1, 2, 1, 1)));
+    compare_with_command(&dir.join("sub"), &status);
+  }
+
+  #[test]
+  fn behind() {
+    let dir = fixture("behind", &["a.txt"]);
+
+    let status = current_status(&dir).unwrap().unwrap();
+    assert_eq!(status.branch, "stable");
+    assert!(status.behind);
+    assert!(status.tags.is_empty());
+    assert!(status.diff.is_none());
+    assert!(status.rev.starts_with("1:"));
+    compare_with_command(&dir, &status);
+
+    // the content must be compared with the revlog if only the modified time differs.
+    filetime::set_file_mtime(dir.join("a.txt"), FileTime::now()).unwrap();
+    assert!(current_status(&dir).is_err());
+  }
+}
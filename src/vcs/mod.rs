@@ -1,11 +1,21 @@
+pub mod cache;
+pub mod darcs;
 pub mod git;
+#[cfg(feature = "native-git")]
+mod git_native;
 pub mod hg;
+#[cfg(feature = "native-hg")]
+mod hg_native;
 pub mod svn;
 
+use std::fmt;
 use std::io;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
 use url::Url;
+use error::GhqError;
+use util::Output;
 
 pub enum Status {
   Git(git::Status),
@@ -14,38 +24,246 @@ pub enum Status {
 }
 
 impl Status {
-  pub fn prompt(&self, fallback: bool) -> String {
-    let (vcs, prompt_str) = match *self {
-      Status::Git(ref s) => ("git", s.prompt(fallback)),
-      Status::Hg(ref s) => ("hg", s.prompt(fallback)),
-      Status::Svn(ref s) => ("svn", s.prompt(fallback)),
+  pub fn branch(&self) -> &str {
+    match *self {
+      Status::Git(ref s) => &s.branch,
+      Status::Hg(ref s) => &s.branch,
+      Status::Svn(ref s) => &s.branch,
+    }
+  }
+
+  pub fn ahead_by(&self) -> usize {
+    match *self {
+      Status::Git(ref s) => s.ahead_by,
+      _ => 0,
+    }
+  }
+
+  pub fn behind_by(&self) -> usize {
+    match *self {
+      Status::Git(ref s) => s.behind_by,
+      Status::Hg(ref s) => if s.behind { 1 } else { 0 },
+      Status::Svn(ref s) => s.incoming,
+    }
+  }
+
+  // the number of changed files tracked by the VCS.
+  pub fn changes(&self) -> usize {
+    fn count(d: &git::DiffInfo) -> usize {
+      d.added + d.modified + d.renamed + d.copied + d.deleted + d.unmerged
+    }
+
+    match *self {
+      Status::Git(ref s) => {
+        s.index.as_ref().map(count).unwrap_or(0) + s.working.as_ref().map(count).unwrap_or(0)
+      }
+      Status::Hg(ref s) => {
+        s.diff
+          .as_ref()
+          .map(|d| d.added + d.modified + d.deleted + d.missing + d.renamed)
+          .unwrap_or(0)
+      }
+      Status::Svn(ref s) => {
+        s.diff
+          .as_ref()
+          .map(|d| {
+            d.added + d.modified + d.replaced + d.deleted + d.missing + d.conflicted + d.obstructed
+          })
+          .unwrap_or(0)
+      }
+    }
+  }
+
+  pub fn untracked(&self) -> usize {
+    match *self {
+      Status::Git(ref s) => s.untracked,
+      Status::Hg(ref s) => s.diff.as_ref().map(|d| d.untracked).unwrap_or(0),
+      Status::Svn(ref s) => s.diff.as_ref().map(|d| d.untracked).unwrap_or(0),
+    }
+  }
+
+  pub fn stash_count(&self) -> usize {
+    match *self {
+      Status::Git(ref s) => s.stash_count,
+      _ => 0,
+    }
+  }
+
+  // the number of submodules which have changes or commits not recorded in the index.
+  pub fn submodules(&self) -> usize {
+    match *self {
+      Status::Git(ref s) => s.submodules,
+      _ => 0,
+    }
+  }
+
+  // HEAD points to a commit (or a tag) instead of a branch.
+  pub fn detached(&self) -> bool {
+    match *self {
+      Status::Git(ref s) => s.branch.starts_with('('),
+      _ => false,
+    }
+  }
+
+  // the operation in progress, such as rebase and merge.
+  pub fn operation(&self) -> Option<&str> {
+    match *self {
+      Status::Git(ref s) => s.operation.as_ref().map(|s| s.as_str()),
+      _ => None,
+    }
+  }
+
+  // the states which may need attention, e.g. before the workspace is discarded.
+  pub fn states(&self) -> Vec<State> {
+    let mut states = Vec::new();
+    if self.changes() > 0 {
+      states.push(State::Dirty);
+    }
+    if self.untracked() > 0 {
+      states.push(State::Untracked);
+    }
+    if self.ahead_by() > 0 {
+      states.push(State::Ahead);
+    }
+    if self.behind_by() > 0 {
+      states.push(State::Behind);
+    }
+    if self.detached() {
+      states.push(State::Detached);
+    }
+    if self.operation().is_some() {
+      states.push(State::InProgress);
+    }
+    if self.stash_count() > 0 {
+      states.push(State::Stash);
+    }
+    if self.submodules() > 0 {
+      states.push(State::Submodules);
+    }
+    states
+  }
+}
+
+impl Encodable for Status {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    match *self {
+      Status::Git(ref st) => st.encode(s),
+      Status::Hg(ref st) => st.encode(s),
+      Status::Svn(ref st) => st.encode(s),
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum State {
+  Dirty,
+  Untracked,
+  Ahead,
+  Behind,
+  Detached,
+  InProgress,
+  Stash,
+  Submodules,
+  // the status could not be collected, e.g. the command of the VCS failed.
+  Unknown,
+}
+
+impl fmt::Display for State {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let s = match *self {
+      State::Dirty => "dirty",
+      State::Untracked => "untracked",
+      State::Ahead => "ahead",
+      State::Behind => "behind",
+      State::Detached => "detached",
+      State::InProgress => "in-progress",
+      State::Stash => "stash",
+      State::Submodules => "submodules",
+      State::Unknown => "unknown",
     };
+    f.write_str(s)
+  }
+}
+
+impl FromStr for State {
+  type Err = ();
+
+  fn from_str(s: &str) -> Result<State, ()> {
+    match s {
+      "dirty" => Ok(State::Dirty),
+      "untracked" => Ok(State::Untracked),
+      "ahead" => Ok(State::Ahead),
+      "behind" => Ok(State::Behind),
+      "detached" => Ok(State::Detached),
+      "in-progress" => Ok(State::InProgress),
+      "stash" => Ok(State::Stash),
+      "submodules" => Ok(State::Submodules),
+      "unknown" => Ok(State::Unknown),
+      _ => Err(()),
+    }
+  }
+}
 
-    format!("[{}]({})", vcs, prompt_str)
+impl Encodable for State {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    s.emit_str(&self.to_string())
   }
 }
 
+// Retrieve the status of the repository which contains `wd`.
 pub fn current_status(wd: &Path) -> Result<Option<Status>, io::Error> {
-  if wd.join(".git").exists() {
-    git::current_status().map(|s| s.map(|s| Status::Git(s)))
-  } else if wd.join(".hg").exists() {
-    hg::current_status().map(|s| s.map(|s| Status::Hg(s)))
-  } else if wd.join(".svn").exists() {
-    svn::current_status().map(|s| s.map(|s| Status::Svn(s)))
-  } else if let Some(parent) = wd.parent() {
-    current_status(parent)
-  } else {
-    Ok(None)
+  match repository_root(wd) {
+    Some(root) => status_of(root),
+    None => Ok(None),
   }
 }
 
-pub trait Prompt {
-  fn prompt(&self, fallback: bool) -> String;
+// The root directory of the repository which contains `wd`.
+pub fn repository_root(wd: &Path) -> Option<&Path> {
+  wd.ancestors().find(|dir| VCS::detect(dir).is_some())
+}
+
+// Retrieve the status of the repository at `path`, without looking into parent directories.
+pub fn status_of(path: &Path) -> Result<Option<Status>, io::Error> {
+  match VCS::detect(path) {
+    Some(VCS::Git) => git::current_status(path).map(|s| s.map(Status::Git)),
+    Some(VCS::Hg) => hg::current_status(path).map(|s| s.map(Status::Hg)),
+    Some(VCS::Svn) => svn::current_status(path).map(|s| s.map(Status::Svn)),
+    _ => Ok(None),
+  }
+}
+
+// The status of the repository at `path` which is cheap to get, e.g. the branch only.
+// `None` if the VCS has nothing to show without running commands.
+pub fn partial_status(path: &Path) -> Result<Option<Status>, io::Error> {
+  match VCS::detect(path) {
+    Some(VCS::Git) => git::partial_status(path).map(|s| s.map(Status::Git)),
+    Some(VCS::Hg) => hg::partial_status(path).map(|s| s.map(Status::Hg)),
+    _ => Ok(None),
+  }
+}
+
+// Files whose modified times change along with the status of the repository at `path`.
+pub fn key_files(path: &Path) -> Vec<PathBuf> {
+  match VCS::detect(path) {
+    Some(VCS::Git) => git::key_files(path),
+    Some(VCS::Hg) => hg::key_files(path),
+    Some(VCS::Svn) => svn::key_files(path),
+    _ => Vec::new(),
+  }
 }
 
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Default)]
+pub struct CloneOptions {
+  pub depth: Option<u32>,
+  pub branch: Option<String>,
+  pub bare: bool,
+  pub recursive: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum VCS {
   Git,
   Svn,
@@ -54,7 +272,6 @@ pub enum VCS {
 }
 
 impl VCS {
-  #[allow(dead_code)]
   pub fn detect<P: AsRef<Path>>(path: P) -> Option<VCS> {
     vec![".git", ".svn", ".hg", "_darcs"]
       .into_iter()
@@ -63,8 +280,152 @@ impl VCS {
       .and_then(|s| s.parse().ok())
   }
 
-  pub fn clone_repository(&self, url: &Url, dest: &Path) -> io::Result<()> {
-    git::clone(url, dest, None).map(|_| ())
+  // guess the VCS from the scheme (and user) of a remote URL.
+  pub fn from_url(url: &Url) -> Option<VCS> {
+    match url.scheme() {
+      "svn" | "svn+ssh" => Some(VCS::Svn),
+      "git" | "git+ssh" | "ssh+git" => Some(VCS::Git),
+      "ssh" if url.username() == "hg" => Some(VCS::Hg),
+      "ssh" if url.username() == "git" => Some(VCS::Git),
+      _ => None,
+    }
+  }
+
+  // inspect a repository on the local filesystem, either a working copy or a bare one.
+  pub fn probe<P: AsRef<Path>>(path: P) -> Option<VCS> {
+    let path = path.as_ref();
+    if let Some(vcs) = VCS::detect(path) {
+      Some(vcs)
+    } else if path.join("HEAD").is_file() && path.join("objects").is_dir() &&
+              path.join("refs").is_dir() {
+      Some(VCS::Git)
+    } else if path.join("format").is_file() && path.join("db").is_dir() {
+      // created by `svnadmin create`
+      Some(VCS::Svn)
+    } else if path.join("00changelog.i").is_file() && path.join("store").is_dir() {
+      // the content of `.hg`
+      Some(VCS::Hg)
+    } else {
+      None
+    }
+  }
+
+  pub fn clone_repository(&self,
+                          url: &Url,
+                          dest: &Path,
+                          opts: &CloneOptions,
+                          out: &mut Output)
+                          -> Result<(), GhqError> {
+    self.check_options(opts)?;
+    let status = match *self {
+      VCS::Git => git::clone(url, dest, opts, out),
+      VCS::Hg => hg::clone(url, dest, opts, out),
+      VCS::Svn => svn::checkout(url, dest, out),
+      VCS::Darcs => darcs::get(url, dest, opts, out),
+    }?;
+    check_status(self, status).map_err(Into::into)
+  }
+
+  // reject options which cannot be mapped to the VCS.
+  // subrepositories of hg and externals of svn are always retrieved, so `recursive` is allowed.
+  fn check_options(&self, opts: &CloneOptions) -> Result<(), GhqError> {
+    match *self {
+      VCS::Git => Ok(()),
+      VCS::Hg if opts.depth.is_some() => Err("shallow clone is not supported for hg".into()),
+      VCS::Hg => Ok(()),
+      VCS::Svn if opts.depth.is_some() => Err("shallow clone is not supported for svn".into()),
+      VCS::Svn if opts.branch.is_some() => {
+        Err("--branch is not supported for svn; specify the URL of the branch instead".into())
+      }
+      VCS::Svn if opts.bare => Err("--bare is not supported for svn".into()),
+      VCS::Svn => Ok(()),
+      VCS::Darcs if opts.depth.is_some() => Err("shallow clone is not supported for darcs".into()),
+      VCS::Darcs if opts.branch.is_some() => Err("--branch is not supported for darcs".into()),
+      VCS::Darcs if opts.recursive => Err("--recursive is not supported for darcs".into()),
+      VCS::Darcs => Ok(()),
+    }
+  }
+
+  pub fn update_repository(&self, dest: &Path, out: &mut Output) -> io::Result<()> {
+    let status = match *self {
+      VCS::Git => git::update(dest, out),
+      VCS::Hg => hg::update(dest, out),
+      VCS::Svn => svn::update(dest, out),
+      VCS::Darcs => darcs::pull(dest, out),
+    }?;
+    check_status(self, status)
+  }
+
+  // move the working copy to the revision, which is given by `revision()`.
+  pub fn update_to_revision(&self,
+                            dest: &Path,
+                            revision: &str,
+                            out: &mut Output)
+                            -> Result<(), GhqError> {
+    let status = match *self {
+      VCS::Git => git::update_to(dest, revision, out),
+      VCS::Hg => hg::update_to(dest, revision, out),
+      VCS::Svn => svn::update_to(dest, revision, out),
+      VCS::Darcs => return Err("checking out a revision is not supported for darcs".into()),
+    }?;
+    check_status(self, status).map_err(Into::into)
+  }
+
+  // retrieve the identifier of current revision in the working copy.
+  pub fn revision(&self, dest: &Path) -> io::Result<Option<String>> {
+    match *self {
+      VCS::Git => git::revision(dest),
+      VCS::Hg => hg::revision(dest),
+      VCS::Svn => svn::revision(dest),
+      VCS::Darcs => darcs::revision(dest),
+    }
+  }
+
+  // the number of commits which exist only in the local repository, or `None` if it cannot be
+  // told (e.g. Darcs).
+  pub fn unpushed_commits(&self, dest: &Path) -> io::Result<Option<usize>> {
+    match *self {
+      VCS::Git => git::unpushed_commits(dest),
+      VCS::Hg => hg::unpushed_commits(dest),
+      // commits are made on the server.
+      VCS::Svn => Ok(Some(0)),
+      VCS::Darcs => Ok(None),
+    }
+  }
+
+  // retrieve the URL of the default remote repository, e.g. `origin` of Git.
+  pub fn remote_url(&self, dest: &Path) -> io::Result<Option<String>> {
+    match *self {
+      VCS::Git => git::remote_url(dest),
+      VCS::Hg => hg::remote_url(dest),
+      VCS::Svn => svn::remote_url(dest),
+      VCS::Darcs => darcs::remote_url(dest),
+    }
+  }
+}
+
+impl fmt::Display for VCS {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    let s = match *self {
+      VCS::Git => "git",
+      VCS::Svn => "svn",
+      VCS::Hg => "hg",
+      VCS::Darcs => "darcs",
+    };
+    f.write_str(s)
+  }
+}
+
+impl Encodable for VCS {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    s.emit_str(&self.to_string())
+  }
+}
+
+impl Decodable for VCS {
+  fn decode<D: Decoder>(d: &mut D) -> Result<VCS, D::Error> {
+    let s = d.read_str()?;
+    s.parse().map_err(|_| d.error(&format!("unknown VCS: {}", s)))
   }
 }
 
@@ -82,8 +443,56 @@ impl FromStr for VCS {
   }
 }
 
-pub fn is_vcs_subdir(path: &Path) -> bool {
-  [".git", ".svn", ".hg", "_darcs"]
-    .into_iter()
-    .any(|vcs| path.join("..").join(vcs).exists())
+fn check_status(vcs: &VCS, status: i32) -> io::Result<()> {
+  if status == 0 {
+    Ok(())
+  } else {
+    Err(io::Error::new(io::ErrorKind::Other,
+                       format!("{} exited with status {}", vcs, status)))
+  }
+}
+
+
+#[cfg(test)]
+mod test_states {
+  use super::{State, Status, git};
+
+  #[test]
+  fn git_states() {
+    let status = Status::Git(git::Status {
+      branch: "(v1.0.0)".to_owned(),
+      ahead_by: 2,
+      untracked: 1,
+      operation: Some("REBASE-i".to_owned()),
+      ..git::Status::default()
+    });
+    assert_eq!(status.states(),
+               vec![State::Untracked, State::Ahead, State::Detached, State::InProgress]);
+
+    let clean = Status::Git(git::Status { branch: "master".to_owned(), ..git::Status::default() });
+    assert!(clean.states().is_empty());
+
+    let submodules = Status::Git(git::Status { submodules: 1, ..git::Status::default() });
+    assert_eq!(submodules.states(), vec![State::Submodules]);
+  }
+}
+
+
+#[cfg(test)]
+mod test_clone_options {
+  use super::{CloneOptions, VCS};
+
+  #[test]
+  fn unsupported() {
+    let shallow = CloneOptions { depth: Some(1), ..CloneOptions::default() };
+    let bare = CloneOptions { bare: true, ..CloneOptions::default() };
+    let branch = CloneOptions { branch: Some("dev".to_owned()), ..CloneOptions::default() };
+
+    assert!(VCS::Git.check_options(&shallow).is_ok());
+    assert!(VCS::Hg.check_options(&shallow).is_err());
+    assert!(VCS::Hg.check_options(&bare).is_ok());
+    assert!(VCS::Svn.check_options(&bare).is_err());
+    assert!(VCS::Svn.check_options(&branch).is_err());
+    assert!(VCS::Darcs.check_options(&bare).is_ok());
+  }
 }
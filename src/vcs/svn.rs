@@ -1,38 +1,40 @@
 use std::io;
-use vcs::Prompt;
+use std::path::{Path, PathBuf};
+use url::Url;
+use prompt::{Fields, Symbols};
 use util::*;
 
-#[derive(Default)]
-struct Diff {
-  untracked: usize,
-  ignored: usize,
-  added: usize,
-  modified: usize,
-  replaced: usize,
-  deleted: usize,
-  missing: usize,
-  conflicted: usize,
-  obstructed: usize,
+#[derive(Default, RustcEncodable, RustcDecodable)]
+pub struct Diff {
+  pub untracked: usize,
+  pub ignored: usize,
+  pub added: usize,
+  pub modified: usize,
+  pub replaced: usize,
+  pub deleted: usize,
+  pub missing: usize,
+  pub conflicted: usize,
+  pub obstructed: usize,
 }
 
-#[derive(Default)]
+#[derive(Default, RustcEncodable, RustcDecodable)]
 pub struct Status {
-  diff: Option<Diff>,
-  external: usize,
-  incoming: usize,
-  branch: String,
-  revision: String,
-  incoming_revision: usize,
+  pub diff: Option<Diff>,
+  pub external: usize,
+  pub incoming: usize,
+  pub branch: String,
+  pub revision: String,
+  pub incoming_revision: usize,
 }
 
-pub fn current_status() -> io::Result<Option<Status>> {
+pub fn current_status(wd: &Path) -> io::Result<Option<Status>> {
   let mut s = Status::default();
 
   let mut has_diff = false;
   let mut diff = Diff::default();
 
   // TODO: add '-u' option
-  let lines = get_lines("svn", &["status", "--ignore-externals"])?;
+  let lines = get_lines("svn", &["status", "--ignore-externals"], Some(wd))?;
   for line in lines {
     if line.starts_with("Status") {
       s.incoming_revision = line.replace("Status against revision:", "").trim().parse().unwrap();
@@ -98,15 +100,20 @@ pub fn current_status() -> io::Result<Option<Status>> {
 
   s.diff = if has_diff { Some(diff) } else { None };
 
-  let branch_info = get_branch_info()?;
+  let branch_info = get_branch_info(wd)?;
   s.branch = branch_info.0;
   s.revision = branch_info.1;
 
   Ok(Some(s))
 }
 
-fn get_branch_info() -> io::Result<(String, String)> {
-  let info = get_lines("svn", &["info"])?;
+// files which are updated whenever the status is likely to change.
+pub fn key_files(wd: &Path) -> Vec<PathBuf> {
+  vec![wd.join(".svn/wc.db")]
+}
+
+fn get_branch_info(wd: &Path) -> io::Result<(String, String)> {
+  let info = get_lines("svn", &["info"], Some(wd))?;
 
   let url = info[3].replace("Relative URL: ^/", "");
   let revision = info[6].replace("Revision: ", "");
@@ -132,29 +139,65 @@ fn get_branch_info() -> io::Result<(String, String)> {
   Ok((branch, revision))
 }
 
-impl Prompt for Status {
-  fn prompt(&self, _: bool) -> String {
-    let mut ret = String::new();
+impl Fields for Status {
+  fn field(&self, name: &str, _: &Symbols) -> Option<String> {
+    let value = match name {
+      "branch" => self.branch.clone(),
+      "revision" => self.revision.clone(),
+      "incoming" => self.incoming.to_string(),
+      "incoming_revision" => self.incoming_revision.to_string(),
+      "external" => self.external.to_string(),
+      "upstream_symbol" => String::new(),
+      "diff" => self.diff.is_some().to_string(),
+      _ if name.starts_with("diff.") => {
+        let clean = Diff::default();
+        let diff = self.diff.as_ref().unwrap_or(&clean);
+        match &name[5..] {
+          "untracked" => diff.untracked,
+          "ignored" => diff.ignored,
+          "added" => diff.added,
+          "modified" => diff.modified,
+          "replaced" => diff.replaced,
+          "deleted" => diff.deleted,
+          "missing" => diff.missing,
+          "conflicted" => diff.conflicted,
+          "obstructed" => diff.obstructed,
+          "changed" => diff.modified + diff.replaced,
+          "conflicts" => diff.conflicted + diff.obstructed,
+          _ => return None,
+        }
+        .to_string()
+      }
+      _ => return None,
+    };
+    Some(value)
+  }
+}
 
-    ret.push_str(&self.branch);
-    ret.push_str(&format!("@{}", self.revision));
+pub fn checkout(url: &Url, dest: &Path, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("svn", &["checkout", url.as_str(), dest.to_str().unwrap()], None)
+}
 
-    if let Some(ref diff) = self.diff {
-      ret.push_str(&format!("|+{}", diff.added));
-      ret.push_str(&format!(" ~{}", diff.modified + diff.replaced));
-      ret.push_str(&format!(" -{}", diff.deleted));
-      ret.push_str(&format!(" ?{}", diff.untracked));
-      ret.push_str(&format!(" !{}", diff.missing));
-      ret.push_str(&format!(" C{}", diff.conflicted + diff.obstructed));
-    }
-    if self.incoming > 0 {
-      ret.push_str(&format!("|In{}@{}", self.incoming, self.incoming_revision));
-    }
+pub fn update(dest: &Path, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("svn", &["update"], Some(dest))
+}
 
-    if self.external > 0 {
-      ret.push_str(&format!("|Ex{}", self.external));
-    }
+pub fn update_to(dest: &Path, revision: &str, out: &mut Output) -> Result<i32, io::Error> {
+  out.exec("svn", &["update", "--revision", revision], Some(dest))
+}
 
-    ret
-  }
+pub fn revision(dest: &Path) -> Result<Option<String>, io::Error> {
+  get_lines("svn", &["info", dest.to_str().unwrap()], None).map(|lines| {
+    lines.into_iter()
+      .find(|line| line.starts_with("Revision: "))
+      .map(|line| line.replace("Revision: ", ""))
+  })
+}
+
+pub fn remote_url(dest: &Path) -> Result<Option<String>, io::Error> {
+  get_lines("svn", &["info", dest.to_str().unwrap()], None).map(|lines| {
+    lines.into_iter()
+      .find(|line| line.starts_with("URL: "))
+      .map(|line| line.replace("URL: ", ""))
+  })
 }
@@ -1,26 +1,39 @@
-use std::fs;
 use std::path::{Path, PathBuf};
 use walkdir::{WalkDir, WalkDirIterator};
 
-use config::Config;
+use config::{Config, expand_full};
 use repository::*;
 use error::GhqError;
-use vcs;
+use host::Hosts;
+use query::Query;
+use util::{Output, same_path};
+use vcs::{CloneOptions, VCS};
 
 
 pub struct Workspace {
+  config: Config,
+  hosts: Hosts,
   repos: Vec<(PathBuf, Vec<Repository>)>,
 }
 
 impl Workspace {
   pub fn new(config: Config) -> Workspace {
+    let hosts = Hosts::from_config(&config);
     let repos = config.roots
       .iter()
-      .map(Path::new)
-      .map(|root| (root.to_owned(), collect_local_repos(&root)))
+      .map(|root| Path::new(&root.path))
+      .map(|root| (root.to_owned(), collect_local_repos(&root, &hosts)))
       .collect();
 
-    Workspace { repos: repos }
+    Workspace {
+      config: config,
+      hosts: hosts,
+      repos: repos,
+    }
+  }
+
+  pub fn hosts(&self) -> &Hosts {
+    &self.hosts
   }
 
   pub fn roots(&self) -> Vec<&Path> {
@@ -31,6 +44,48 @@ impl Workspace {
     self.repos.iter().next().map(|root| root.0.as_path())
   }
 
+  // pairs of the name and the path of roots
+  pub fn named_roots(&self) -> Vec<(Option<&str>, &Path)> {
+    self.config
+      .roots
+      .iter()
+      .map(|root| root.name.as_ref().map(|s| s.as_str()))
+      .zip(self.roots())
+      .collect()
+  }
+
+  // choose a root directory by its name, index or path.
+  pub fn select_root(&self,
+                     spec: Option<&str>,
+                     allow_unregistered: bool)
+                     -> Result<PathBuf, GhqError> {
+    let spec = match spec {
+      Some(spec) => spec,
+      None => {
+        return self.default_root()
+          .map(ToOwned::to_owned)
+          .ok_or("Cannot get root directory of the workspace".into())
+      }
+    };
+
+    let roots = self.named_roots();
+    if let Some(&(_, root)) = roots.iter().find(|&&(name, _)| name == Some(spec)) {
+      return Ok(root.to_owned());
+    }
+    if let Some(&(_, root)) = spec.parse::<usize>().ok().and_then(|i| roots.get(i)) {
+      return Ok(root.to_owned());
+    }
+
+    let path = PathBuf::from(expand_full(spec).map_err(|_| "failed to expand the path of root")?);
+    if let Some(&(_, root)) = roots.iter().find(|&&(_, root)| same_path(root, &path)) {
+      Ok(root.to_owned())
+    } else if allow_unregistered {
+      Ok(path)
+    } else {
+      Err(GhqError::UnregisteredRoot(spec.to_owned()))
+    }
+  }
+
   pub fn map_repo<F, T>(&self, f: F) -> Vec<T>
     where F: Fn(&Repository, &Path) -> T
   {
@@ -41,27 +96,94 @@ impl Workspace {
       .collect()
   }
 
-  // clone a remote repository into the workspace.
-  pub fn clone_from(&self, s: &str) -> Result<(), GhqError> {
-    // get root directory
-    let root = self.default_root()
-      .ok_or("Cannot get root directory of the workspace")?;
-    if !root.exists() {
-      fs::create_dir_all(root)?;
+  pub fn query(&self, query: &Query) -> Vec<(&Path, &Repository)> {
+    self.repos
+      .iter()
+      .flat_map(|&(ref root, ref repos)| repos.iter().map(move |r| (root.as_path(), r)))
+      .filter(|&(_, repo)| query.matches(repo))
+      .collect()
+  }
+
+  // the root which already contains the repository, if any.
+  pub fn locate(&self, repo: &Repository) -> Option<&Path> {
+    self.repos
+      .iter()
+      .find(|&&(_, ref repos)| repos.iter().any(|r| r.full_name() == repo.full_name()))
+      .map(|&(ref root, _)| root.as_path())
+  }
+
+  // find a repository by `host/owner/repo`, `owner/repo` or the name of repository.
+  pub fn lookup(&self, query: &str) -> Result<(&Path, &Repository), GhqError> {
+    let repos = self.query(&Query::new(None));
+
+    // try in order of `host/owner/repo`, `owner/repo` and `repo`.
+    for level in 0..3 {
+      let candidates: Vec<_> = repos.iter()
+        .filter(|&&(_, repo)| match level {
+          0 => repo.full_name() == query,
+          1 => repo.path() == query,
+          _ => repo.name() == query,
+        })
+        .collect();
+      match candidates.len() {
+        0 => continue,
+        1 => return Ok(*candidates[0]),
+        _ => {
+          let names = candidates.iter().map(|&&(_, repo)| repo.full_name()).collect();
+          return Err(GhqError::Ambiguous(query.to_owned(), names));
+        }
+      }
     }
 
-    Repository::from_remote(s)?.clone_into(&root)
+    Err(GhqError::NotFound(query.to_owned()))
+  }
+
+  pub fn resolve_remote(&self, s: &str, vcs: Option<VCS>) -> Result<Repository, GhqError> {
+    let mut repo = Repository::from_remote(s, &self.hosts)?;
+    if let Some(vcs) = vcs {
+      repo = repo.with_vcs(vcs);
+    }
+    Ok(repo)
+  }
+
+  // clone a remote repository into the workspace.
+  pub fn clone_from(&self,
+                    s: &str,
+                    root: &Path,
+                    vcs: Option<VCS>,
+                    opts: &CloneOptions,
+                    update: bool,
+                    out: &mut Output)
+                    -> Result<Outcome, GhqError> {
+    self.resolve_remote(s, vcs)?.clone_into(root, opts, update, out)
+  }
+
+  // move (or link) an existing local repository into the workspace,
+  // at the location derived from its remote URL.
+  pub fn import(&self,
+                dir: &Path,
+                root: &Path,
+                link: bool,
+                dry_run: bool,
+                out: &mut Output)
+                -> Result<Outcome, GhqError> {
+    let vcs = VCS::probe(dir).ok_or("the directory is not a repository")?;
+    let remote = vcs.remote_url(dir)?.ok_or("the remote URL is not configured")?;
+    self.resolve_remote(&remote, Some(vcs))?.import_from(dir, root, link, dry_run, out)
   }
 }
 
-fn collect_local_repos<P: AsRef<Path>>(root: P) -> Vec<Repository> {
+fn collect_local_repos<P: AsRef<Path>>(root: P, hosts: &Hosts) -> Vec<Repository> {
   WalkDir::new(&root)
     .follow_links(true)
     .into_iter()
     .filter_entry(|entry| !is_vcs_component(entry.path()))
     .filter_map(|entry| entry.ok())
-    .filter_map(|entry| relative_path(entry.path(), root.as_ref()).ok())
-    .filter_map(|path| Repository::from_local(&path).ok())
+    .filter_map(|entry| {
+      let vcs = VCS::probe(entry.path())?;
+      let path = relative_path(entry.path(), root.as_ref()).ok()?;
+      Repository::from_local(&path, hosts).ok().map(|repo| repo.with_vcs(vcs))
+    })
     .collect()
 }
 
@@ -70,10 +192,187 @@ fn relative_path(path: &Path, root: &Path) -> Result<PathBuf, GhqError> {
   Ok(path.strip_prefix(root)?.to_path_buf())
 }
 
+// check if `path` is placed inside of a repository (including its VCS directory).
 fn is_vcs_component(path: &Path) -> bool {
-  if let Some(path) = path.parent() {
-    [".git", ".svn", ".hg", "_dacrs"].into_iter().any(|v| vcs::is_vcs_subdir(&path.join(v)))
-  } else {
-    false
+  path.parent().and_then(VCS::probe).is_some()
+}
+
+
+#[cfg(test)]
+mod test_collect_local_repos {
+  use std::fs;
+  use super::collect_local_repos;
+  use host::Hosts;
+  use testing::TempDir;
+  use vcs::VCS;
+
+  #[test]
+  fn only_with_vcs_marker() {
+    let root = TempDir::new("collect");
+    fs::create_dir_all(root.join("github.com/hoge/fuga/.git")).unwrap();
+    fs::create_dir_all(root.join("github.com/hoge/fuga/src/foo")).unwrap();
+    fs::create_dir_all(root.join("bitbucket.org/hoge/piyo/.hg")).unwrap();
+    fs::create_dir_all(root.join("github.com/hoge/plain")).unwrap();
+
+    let mut repos: Vec<_> = collect_local_repos(&root, &Hosts::default())
+      .into_iter()
+      .map(|repo| (repo.local_path(""), repo.vcs()))
+      .collect();
+    repos.sort_by(|a, b| a.0.cmp(&b.0));
+
+    assert_eq!(repos.len(), 2);
+    assert_eq!(repos[0].0.to_str(), Some("bitbucket.org/hoge/piyo"));
+    assert_eq!(repos[0].1, VCS::Hg);
+    assert_eq!(repos[1].0.to_str(), Some("github.com/hoge/fuga"));
+    assert_eq!(repos[1].1, VCS::Git);
+  }
+}
+
+
+#[cfg(test)]
+mod test_lookup {
+  use std::path::PathBuf;
+  use super::Workspace;
+  use config::Config;
+  use error::GhqError;
+  use host::Hosts;
+  use repository::Repository;
+
+  fn workspace() -> Workspace {
+    let repos = vec!["github.com/hoge/fuga", "github.com/piyo/fuga", "gitlab.com/hoge/fuga",
+                     "github.com/hoge/foo"]
+      .into_iter()
+      .map(|s| Repository::from_local(s, &Hosts::default()).unwrap())
+      .collect();
+    Workspace {
+      config: Config::default(),
+      hosts: Hosts::default(),
+      repos: vec![(PathBuf::from("/ghq"), repos)],
+    }
+  }
+
+  #[test]
+  fn unique() {
+    let ws = workspace();
+    assert_eq!(ws.lookup("foo").unwrap().1.full_name(), "github.com/hoge/foo");
+    assert_eq!(ws.lookup("piyo/fuga").unwrap().1.full_name(), "github.com/piyo/fuga");
+    assert_eq!(ws.lookup("gitlab.com/hoge/fuga").unwrap().1.full_name(),
+               "gitlab.com/hoge/fuga");
+  }
+
+  #[test]
+  fn ambiguous() {
+    match workspace().lookup("hoge/fuga") {
+      Err(GhqError::Ambiguous(_, candidates)) => {
+        assert_eq!(candidates, vec!["github.com/hoge/fuga", "gitlab.com/hoge/fuga"])
+      }
+      _ => panic!("should be ambiguous"),
+    }
+  }
+
+  #[test]
+  fn not_found() {
+    match workspace().lookup("bar") {
+      Err(GhqError::NotFound(_)) => (),
+      _ => panic!("should not be found"),
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod test_select_root {
+  use std::path::{Path, PathBuf};
+  use super::Workspace;
+  use config::{Config, Root, Roots};
+  use error::GhqError;
+  use host::Hosts;
+
+  fn workspace() -> Workspace {
+    let roots = vec![("main", "/ghq"), ("work", "/work")];
+    Workspace {
+      config: Config {
+        roots: Roots(roots.iter()
+          .map(|&(name, path)| {
+            Root {
+              name: Some(name.to_owned()),
+              path: path.to_owned(),
+            }
+          })
+          .collect()),
+        ..Config::default()
+      },
+      hosts: Hosts::default(),
+      repos: roots.iter().map(|&(_, path)| (PathBuf::from(path), Vec::new())).collect(),
+    }
+  }
+
+  #[test]
+  fn by_name_index_and_path() {
+    let ws = workspace();
+    assert_eq!(ws.select_root(None, false).unwrap(), Path::new("/ghq"));
+    assert_eq!(ws.select_root(Some("work"), false).unwrap(), Path::new("/work"));
+    assert_eq!(ws.select_root(Some("1"), false).unwrap(), Path::new("/work"));
+    assert_eq!(ws.select_root(Some("/work/"), false).unwrap(), Path::new("/work"));
+  }
+
+  #[test]
+  fn unregistered() {
+    let ws = workspace();
+    match ws.select_root(Some("/tmp/other"), false) {
+      Err(GhqError::UnregisteredRoot(_)) => (),
+      _ => panic!("should be rejected"),
+    }
+    assert_eq!(ws.select_root(Some("/tmp/other"), true).unwrap(),
+               Path::new("/tmp/other"));
+  }
+}
+
+
+#[cfg(test)]
+mod test_import {
+  use std::fs::{self, File};
+  use std::io::Write;
+  use std::path::Path;
+  use super::Workspace;
+  use config::Config;
+  use host::Hosts;
+  use repository::Outcome;
+  use testing::TempDir;
+  use util::Output;
+
+  fn checkout(dir: &Path, url: &str) {
+    fs::create_dir_all(dir.join(".git")).unwrap();
+    File::create(dir.join(".git/config"))
+      .unwrap()
+      .write_all(format!("[remote \"origin\"]\n\turl = {}\n", url).as_bytes())
+      .unwrap();
+  }
+
+  #[test]
+  fn move_into_root() {
+    let dir = TempDir::new("import");
+    let root = dir.join("ghq");
+    let src = dir.join("src/fuga");
+    checkout(&src, "https://github.com/hoge/fuga.git");
+
+    let ws = Workspace {
+      config: Config::default(),
+      hosts: Hosts::default(),
+      repos: Vec::new(),
+    };
+    let mut out = Output::Capture(String::new());
+    let dest = root.join("github.com/hoge/fuga");
+
+    assert_eq!(ws.import(&src, &root, false, true, &mut out).unwrap(), Outcome::Skipped);
+    assert!(src.exists() && !dest.exists());
+
+    assert_eq!(ws.import(&src, &root, false, false, &mut out).unwrap(), Outcome::Moved);
+    assert!(!src.exists() && dest.join(".git/config").exists());
+
+    // refuse to overwrite the existing repository
+    checkout(&src, "git@github.com:hoge/fuga.git");
+    assert!(ws.import(&src, &root, false, false, &mut out).is_err());
+    assert!(src.exists());
   }
 }
@@ -0,0 +1,128 @@
+#!/bin/sh
+# Regenerate the fixtures of the in-process Git backend (`src/vcs/git_native`).
+#
+# `.git` and `.gitignore` cannot be checked in as they are, so they are stored as `dot-git` and
+# `dot-gitignore`, and renamed back when the tests copy the fixtures.
+set -eu
+
+cd "$(dirname "$0")"
+export GIT_AUTHOR_NAME=ghqrs GIT_AUTHOR_EMAIL=ghqrs@example.com
+export GIT_COMMITTER_NAME=ghqrs GIT_COMMITTER_EMAIL=ghqrs@example.com
+export GIT_AUTHOR_DATE="1500000000 +0000" GIT_COMMITTER_DATE="1500000000 +0000"
+export GIT_CONFIG_NOSYSTEM=1 HOME=/nonexistent
+
+init() {
+  rm -rf "$1"
+  git init --quiet "$1"
+  git -C "$1" symbolic-ref HEAD refs/heads/main
+}
+
+commit() {
+  dir=$1
+  message=$2
+  shift 2
+  while [ $# -gt 0 ]; do
+    mkdir -p "$(dirname "$dir/$1")"
+    printf '%s' "$2" > "$dir/$1"
+    git -C "$dir" add "$1"
+    shift 2
+  done
+  git -C "$dir" commit --quiet --allow-empty -m "$message"
+}
+
+# drop what the tests never read, and rename the files which Git refuses to track.
+finish() {
+  rm -rf "$1/.git/hooks" "$1/.git/description" "$1/.git/COMMIT_EDITMSG" "$1/.git/ORIG_HEAD"
+  if [ -d "$1/.git/logs" ]; then
+    find "$1/.git/logs" -type f ! -path '*/logs/refs/stash' -delete
+    find "$1/.git/logs" -type d -empty -delete
+  fi
+  find "$1" -name .gitignore -exec sh -c 'mv "$1" "$(dirname "$1")/dot-gitignore"' _ {} \;
+  mv "$1/.git" "$1/dot-git"
+}
+
+# staged, unstaged and untracked changes with a stash
+init changes
+commit changes first a.txt a b.txt b c.txt c d.txt d sub/e.txt e
+printf a2 > changes/a.txt
+git -C changes stash --quiet
+printf a3 > changes/a.txt
+printf new > changes/new.txt
+git -C changes add new.txt
+git -C changes rm --quiet b.txt
+git -C changes mv c.txt c2.txt
+rm changes/d.txt
+printf e2 > changes/sub/e.txt
+git -C changes add sub/e.txt
+printf e3 > changes/sub/e.txt
+printf u > changes/untracked.txt
+printf i > changes/ita.txt
+git -C changes add --intent-to-add ita.txt
+finish changes
+
+# ignore rules; empty directories and a nested repository are created by the test
+init ignored
+commit ignored first .gitignore "*.log
+/build/
+!keep.log
+" src/main.rs "" src/.gitignore "gen/
+"
+for path in a.log keep.log build/out src/build/out src/gen/out src/lib.rs logs/a.log docs/a/b/c.md
+do
+  mkdir -p "$(dirname "ignored/$path")"
+  : > "ignored/$path"
+done
+finish ignored
+
+# a detached HEAD at a lightweight tag, with packed objects and refs
+init detached
+commit detached first a.txt a
+git -C detached tag -a -m annotated v1.0
+commit detached second a.txt b
+git -C detached tag light
+commit detached third a.txt c
+git -C detached checkout --quiet light
+git -C detached gc --quiet
+finish detached
+
+# a branch diverged from its upstream, with deltas in the pack
+rm -rf upstream diverged
+init upstream
+for n in $(seq 10 40); do
+  printf '%s' "$n" > "upstream/f$n.txt"
+done
+git -C upstream add .
+i=0
+while [ $i -lt 20 ]; do
+  content=$(seq 0 $((i * 10)))
+  commit upstream "commit $i" a.txt "$content"
+  i=$((i + 1))
+done
+git clone --quiet upstream diverged
+git -C diverged gc --quiet
+commit upstream "upstream 1" b.txt b
+commit upstream "upstream 2" c.txt c
+commit diverged local d.txt d
+git -C diverged fetch --quiet
+git -C diverged config remote.origin.url https://example.com/upstream.git
+rm -rf upstream
+finish diverged
+
+# conflicts of a merge
+init unmerged
+commit unmerged first a.txt a b.txt b
+git -C unmerged checkout --quiet -b topic
+commit unmerged topic a.txt topic c.txt topic
+git -C unmerged rm --quiet b.txt
+commit unmerged remove
+git -C unmerged checkout --quiet main
+commit unmerged main a.txt main b.txt main c.txt main
+! git -C unmerged merge --quiet topic > /dev/null
+finish unmerged
+
+# no commits yet
+init initial
+printf a > initial/a.txt
+git -C initial add a.txt
+printf b > initial/b.txt
+finish initial
@@ -0,0 +1,80 @@
+#!/bin/sh
+# Regenerate the fixtures of the in-process Mercurial reader (`src/vcs/hg_native.rs`).
+#
+# The reader supports neither dirstate-v2 nor shared stores, so they are disabled in case the
+# installed Mercurial enables them by default. Checkouts don't keep modified times, so the clean
+# files are recorded at a fixed time, which the tests restore after copying the fixtures.
+set -eu
+
+cd "$(dirname "$0")"
+export HGRCPATH=/dev/null HGPLAIN=1 HGUSER="ghqrs <ghqrs@example.com>" TZ=UTC
+# 1500000000, i.e. `MTIME` of the tests
+TOUCH_TIME=201707140240.00
+
+hg() {
+  command hg --config format.use-dirstate-v2=no --config format.use-share-safe=no "$@"
+}
+
+init() {
+  rm -rf "$1"
+  hg init "$1"
+}
+
+commit() {
+  dir=$1
+  message=$2
+  shift 2
+  while [ $# -gt 0 ]; do
+    mkdir -p "$(dirname "$dir/$1")"
+    printf '%s\n' "$2" > "$dir/$1"
+    hg -R "$dir" add --quiet "$dir/$1"
+    shift 2
+  done
+  hg -R "$dir" commit --quiet --date "1500000000 0" -m "$message"
+}
+
+# record the clean files in the dirstate at the fixed time, write the caches of tags and branch
+# heads, and drop what the tests never read.
+finish() {
+  dir=$1
+  shift
+  for path in "$@"; do
+    touch -t "$TOUCH_TIME" "$dir/$path"
+  done
+  hg -R "$dir" status > /dev/null
+  hg -R "$dir" tags > /dev/null
+  hg -R "$dir" heads > /dev/null
+  rm -rf "$dir/.hg/wcache" "$dir/.hg/last-message.txt" "$dir"/.hg/undo* "$dir"/.hg/store/undo*
+  find "$dir/.hg/cache" -type f ! -name tags2-visible ! -name 'branch2-*' -delete
+}
+
+# a named branch whose working directory is behind its head
+init behind
+commit behind first a.txt aaaa
+hg -R behind branch --quiet stable
+commit behind branch
+commit behind second b.txt bbbb
+hg -R behind update --quiet 1
+finish behind a.txt
+
+# changes of every kind, with local tags, an active bookmark and ignored files
+init changes
+commit changes first .hgignore "syntax: glob
+*.log
+build" a.txt a b.txt bbbb c.txt c f.txt ffff old.txt old sub/g.txt g
+commit changes second a.txt aaaa
+commit changes third sub/g.txt gggg
+hg -R changes tag --quiet --local -r 1 v1.0
+hg -R changes tag --quiet --local v2.0
+hg -R changes bookmark --quiet feature
+printf 'bbbb changed\n' > changes/b.txt
+rm changes/c.txt
+printf 'dddd\n' > changes/d.txt
+hg -R changes add --quiet changes/d.txt
+hg -R changes remove --quiet changes/f.txt
+hg -R changes rename --quiet changes/old.txt changes/new.txt
+mkdir -p changes/build
+for path in u1.txt sub/u2.txt x.log sub/y.log build/out.o; do
+  printf '%s\n' "$(basename "$path" | cut -c1)" > "changes/$path"
+done
+finish changes .hgignore a.txt b.txt sub/g.txt